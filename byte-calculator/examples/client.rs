@@ -1,31 +1,737 @@
-use solana_client::rpc_client::RpcClient;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::BorshDeserialize;
+use byte_calculator::CalcEvent;
+use clap::{Parser, ValueEnum};
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_client::RpcClient,
+    rpc_request::RpcError,
+};
 use solana_sdk::{
     instruction::Instruction,
     pubkey::Pubkey,
-    signature::{Keypair, Signer},
-    transaction::Transaction,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status_client_types::{UiTransactionEncoding, UiTransactionReturnData};
+use std::{
+    error::Error,
+    path::PathBuf,
+    time::{Duration, Instant},
 };
-use std::str::FromStr;
-
-fn main()  {
-    let program_id = Pubkey::from_str("yzKg3w29hwBimp9Fp2PFCge9CZSJfJm6Ndv86G9mr4N").unwrap();
-    
-    let client = RpcClient::new("https://api.devnet.solana.com");
-    let payer = Keypair::new();
-    
-    
-    println!("Getting SOL for transactions...");
-    let airdrop_sig = client.request_airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
-    println!("Airdrop: {}", airdrop_sig);
-    
-    // Wait a moment for airdrop
-    std::thread::sleep(std::time::Duration::from_secs(5));
-    
-    // Test all operations
-    test_operation(&client, &payer, &program_id, 0, 15, 7, "Add");
-    test_operation(&client, &payer, &program_id, 1, 20, 8, "Subtract");  
-    test_operation(&client, &payer, &program_id, 2, 6, 4, "Multiply");
-    test_operation(&client, &payer, &program_id, 3, 24, 6, "Divide");
-    test_operation(&client, &payer, &program_id, 4, 17, 5, "Modulus");
-    test_operation(&client, &payer, &program_id, 5, 3, 4, "Power");
-}
\ No newline at end of file
+
+/// Run calculator operations against a deployed byte-calculator: one chosen
+/// with `--op`, or every opcode in turn
+#[derive(Parser)]
+struct Args {
+    /// RPC endpoint to send transactions to
+    #[arg(long, default_value = "https://api.devnet.solana.com")]
+    url: String,
+
+    /// Talk to a local test validator, shorthand for `--url
+    /// http://127.0.0.1:8899`
+    #[arg(long, conflicts_with = "url")]
+    local: bool,
+
+    /// Program ID of the deployed calculator
+    #[arg(long, default_value = "yzKg3w29hwBimp9Fp2PFCge9CZSJfJm6Ndv86G9mr4N")]
+    program_id: Pubkey,
+
+    /// Payer keypair file [default: ~/.config/solana/id.json]
+    #[arg(long)]
+    keypair: Option<PathBuf>,
+
+    /// Simulate each operation and print its logs instead of submitting it
+    #[arg(long)]
+    simulate: bool,
+
+    /// How many times to resend a transaction after a dropped connection or
+    /// an expired blockhash
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    /// Run a single operation instead of the demo sequence
+    #[arg(long, requires = "a")]
+    op: Option<Op>,
+
+    /// Left operand, or the only operand of a unary operation
+    #[arg(long, allow_negative_numbers = true)]
+    a: Option<i64>,
+
+    /// Right operand, ignored by unary operations
+    #[arg(long, allow_negative_numbers = true, default_value_t = 0)]
+    b: i64,
+
+    /// Run every operation in the demo sequence (the default without `--op`)
+    #[arg(long, conflicts_with = "op")]
+    all: bool,
+
+    /// Print each result as a JSON object on its own line instead of a table
+    #[arg(long)]
+    json: bool,
+}
+
+/// RPC endpoint of a `solana-test-validator` on this machine
+const LOCAL_URL: &str = "http://127.0.0.1:8899";
+
+impl Args {
+    /// The RPC endpoint chosen by `--url` or `--local`
+    fn rpc_url(&self) -> &str {
+        if self.local {
+            LOCAL_URL
+        } else {
+            &self.url
+        }
+    }
+}
+
+/// Operations the calculator supports, declared in opcode order
+#[derive(Clone, Copy, ValueEnum)]
+enum Op {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulus,
+    Power,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+    Eq,
+    Lt,
+    Gt,
+    Ne,
+    Sqrt,
+    Abs,
+    Neg,
+    Gcd,
+    Lcm,
+    Divmod,
+}
+
+impl Op {
+    /// The opcode byte selecting this operation
+    fn opcode(self) -> u8 {
+        self as u8
+    }
+
+    /// The name `--op` accepts, also used as `op` in JSON output
+    fn flag_name(self) -> String {
+        let value = self.to_possible_value().expect("No operation is skipped");
+        value.get_name().to_owned()
+    }
+
+    /// Human-readable name for result tables
+    fn name(self) -> &'static str {
+        match self {
+            Op::Add => "Add",
+            Op::Subtract => "Subtract",
+            Op::Multiply => "Multiply",
+            Op::Divide => "Divide",
+            Op::Modulus => "Modulus",
+            Op::Power => "Power",
+            Op::And => "AND",
+            Op::Or => "OR",
+            Op::Xor => "XOR",
+            Op::Shl => "Shift left",
+            Op::Shr => "Shift right",
+            Op::Eq => "Equal",
+            Op::Lt => "Less than",
+            Op::Gt => "Greater than",
+            Op::Ne => "Not equal",
+            Op::Sqrt => "Square root",
+            Op::Abs => "Absolute value",
+            Op::Neg => "Negate",
+            Op::Gcd => "GCD",
+            Op::Lcm => "LCM",
+            Op::Divmod => "Divmod",
+        }
+    }
+
+    /// Bytes of return data the program sets: 1 for a comparison, 16 for a
+    /// quotient and remainder and 8 for any other result
+    fn result_len(self) -> usize {
+        match self {
+            Op::Eq | Op::Lt | Op::Gt | Op::Ne => 1,
+            Op::Divmod => 16,
+            _ => 8,
+        }
+    }
+}
+
+/// Every operation with sample operands, run by `--all`. Unary operations
+/// ignore the right operand.
+const DEMO: [(Op, i64, i64); 21] = [
+    (Op::Add, 15, 7),
+    (Op::Subtract, 20, 8),
+    (Op::Multiply, 6, 4),
+    (Op::Divide, 24, 6),
+    (Op::Modulus, 17, 5),
+    (Op::Power, 3, 4),
+    (Op::And, 6, 3),
+    (Op::Or, 6, 3),
+    (Op::Xor, 6, 3),
+    (Op::Shl, 1, 10),
+    (Op::Shr, 1024, 3),
+    (Op::Eq, 7, 7),
+    (Op::Lt, 3, 9),
+    (Op::Gt, 3, 9),
+    (Op::Ne, 3, 9),
+    (Op::Sqrt, 144, 0),
+    (Op::Abs, -5, 0),
+    (Op::Neg, 7, 0),
+    (Op::Gcd, 48, 36),
+    (Op::Lcm, 4, 6),
+    (Op::Divmod, 17, 5),
+];
+
+/// Where and how to send calculator instructions
+struct Calculator<'a> {
+    client: &'a RpcClient,
+    payer: &'a Keypair,
+    program_id: Pubkey,
+    simulate: bool,
+    retries: u32,
+    json: bool,
+}
+
+/// Lamports requested for a generated payer
+const AIRDROP_LAMPORTS: u64 = 1_000_000_000;
+
+/// Lamports set aside for each planned transaction: the 5000-lamport
+/// signature fee with room for a priority fee
+const LAMPORTS_PER_TRANSACTION: u64 = 10_000;
+
+/// How long to wait for an airdrop to land before giving up
+const AIRDROP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Delay before the first resend; each further attempt waits twice as long
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let url = args.rpc_url();
+
+    let client = RpcClient::new(url.to_owned());
+    if !args.json {
+        println!("Using cluster {}", url);
+    }
+
+    // Use the configured wallet when there is one, otherwise fund a throwaway key
+    let keypair_path = args.keypair.clone().unwrap_or_else(default_keypair_path);
+    let payer = if keypair_path.exists() {
+        let payer = read_keypair_file(&keypair_path)
+            .map_err(|err| format!("Failed to read keypair {}: {}", keypair_path.display(), err))?;
+        if !args.json {
+            println!(
+                "Using payer {} from {}",
+                payer.pubkey(),
+                keypair_path.display()
+            );
+        }
+        payer
+    } else {
+        let payer = Keypair::new();
+        if !args.json {
+            println!(
+                "No keypair at {}, generated a new payer",
+                keypair_path.display()
+            );
+        }
+
+        // A rate-limited faucet is caught by the balance check below
+        match client.request_airdrop(&payer.pubkey(), AIRDROP_LAMPORTS) {
+            Ok(airdrop_sig) => {
+                if !args.json {
+                    println!("Airdrop: {}", airdrop_sig);
+                }
+                if let Err(err) = confirm_airdrop(&client, &payer.pubkey(), AIRDROP_LAMPORTS) {
+                    eprintln!("{}", err);
+                } else if !args.json {
+                    println!("Airdrop confirmed");
+                }
+            }
+            Err(err) => eprintln!("Airdrop request to {} failed: {}", url, err),
+        }
+        payer
+    };
+
+    let planned_transactions = if args.op.is_some() { 1 } else { DEMO.len() };
+    check_balance(&client, &payer.pubkey(), planned_transactions)?;
+
+    let calculator = Calculator {
+        client: &client,
+        payer: &payer,
+        program_id: args.program_id,
+        simulate: args.simulate,
+        retries: args.retries,
+        json: args.json,
+    };
+
+    if !calculator.json {
+        if calculator.simulate {
+            println!("Simulating operations, nothing will be submitted");
+        } else {
+            println!("Submitting operations to {}", url);
+        }
+        println!("{:<16} {:<24} Result", "Operation", "Inputs");
+    }
+    if let (Some(op), Some(left)) = (args.op, args.a) {
+        test_operation(&calculator, op, left, args.b)?;
+    } else {
+        for (op, left, right) in DEMO {
+            test_operation(&calculator, op, left, right)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The Solana CLI's default wallet location
+fn default_keypair_path() -> PathBuf {
+    let home = std::env::var_os("HOME").unwrap_or_default();
+    PathBuf::from(home).join(".config/solana/id.json")
+}
+
+/// Poll the payer's balance until it reflects the airdrop, giving up after
+/// `AIRDROP_TIMEOUT` instead of guessing with a fixed sleep
+fn confirm_airdrop(
+    client: &RpcClient,
+    payer: &Pubkey,
+    lamports: u64,
+) -> Result<(), Box<dyn Error>> {
+    let deadline = Instant::now() + AIRDROP_TIMEOUT;
+    while client.get_balance(payer).unwrap_or(0) < lamports {
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "Airdrop of {} lamports to {} not confirmed after {}s",
+                lamports,
+                payer,
+                AIRDROP_TIMEOUT.as_secs()
+            )
+            .into());
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+    Ok(())
+}
+
+/// Fail before sending anything when the payer can't cover the planned
+/// transactions, naming the account to fund instead of letting the first
+/// transaction fail with an opaque fee error
+fn check_balance(
+    client: &RpcClient,
+    payer: &Pubkey,
+    transactions: usize,
+) -> Result<(), Box<dyn Error>> {
+    let required = LAMPORTS_PER_TRANSACTION * transactions as u64;
+    let balance = client.get_balance(payer)?;
+    if balance < required {
+        return Err(format!(
+            "Payer {} holds {} lamports but {} transactions need about {}. \
+             Fund it manually, e.g. `solana airdrop 1 {}`, and try again",
+            payer, balance, transactions, required, payer
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Send one calculator instruction: opcode followed by two little-endian i64
+/// operands
+fn test_operation(
+    calculator: &Calculator,
+    op: Op,
+    left: i64,
+    right: i64,
+) -> Result<(), Box<dyn Error>> {
+    let Calculator {
+        client,
+        payer,
+        program_id,
+        simulate,
+        retries,
+        json,
+    } = *calculator;
+
+    let mut instruction_data = vec![op.opcode()];
+    instruction_data.extend_from_slice(&left.to_le_bytes());
+    instruction_data.extend_from_slice(&right.to_le_bytes());
+
+    let instruction = Instruction::new_with_bytes(program_id, &instruction_data, vec![]);
+
+    if simulate {
+        let recent_blockhash = client.get_latest_blockhash().inspect_err(|err| {
+            eprintln!("Fetching a blockhash from {} failed: {}", client.url(), err)
+        })?;
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[payer],
+            recent_blockhash,
+        );
+        return simulate_operation(client, &transaction, op, left, right, json);
+    }
+
+    let signature = send_with_retry(client, &[instruction], payer, &[], retries)
+        .inspect_err(|err| eprintln!("{} ({} , {}) failed: {}", op.name(), left, right, err))?;
+
+    let result = fetch_result(client, &signature)?;
+    if json {
+        let output = operation_json(op, left, right, result.as_deref(), Some(&signature));
+        println!("{}", output);
+    } else {
+        let result = result.map_or_else(
+            || String::from("unknown"),
+            |bytes| format_result(op, &bytes),
+        );
+        println!(
+            "{:<16} {:<24} {}",
+            op.name(),
+            format_inputs(op.opcode(), left, right),
+            result
+        );
+    }
+
+    Ok(())
+}
+
+/// Sign and send a transaction, rebuilding it against a fresh blockhash and
+/// backing off exponentially for up to `retries` resends when the failure is
+/// recoverable
+fn send_with_retry(
+    client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+    retries: u32,
+) -> Result<Signature, Box<dyn Error>> {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        let blockhash = client.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        match client.send_and_confirm_transaction(&transaction) {
+            Err(err) if attempt < retries && is_recoverable(&err) => {
+                attempt += 1;
+                // Progress goes to stderr so it never mixes with JSON output
+                eprintln!(
+                    "Send failed ({}), retry {}/{} in {}ms",
+                    err,
+                    attempt,
+                    retries,
+                    backoff.as_millis()
+                );
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Whether a failed send is worth repeating: dropped connections, expired
+/// blockhashes and transactions that never confirmed. A transaction the
+/// program rejected fails the same way every time.
+fn is_recoverable(err: &ClientError) -> bool {
+    match err.get_transaction_error() {
+        Some(TransactionError::BlockhashNotFound) => true,
+        Some(_) => false,
+        None => matches!(
+            err.kind(),
+            ClientErrorKind::Io(_)
+                | ClientErrorKind::Reqwest(_)
+                | ClientErrorKind::RpcError(RpcError::ForUser(_))
+        ),
+    }
+}
+
+/// Run a calculator transaction through `simulate_transaction` and print what
+/// it would have returned, along with its logs. Failures such as overflow are
+/// reported rather than returned so the remaining operations still run.
+fn simulate_operation(
+    client: &RpcClient,
+    transaction: &Transaction,
+    op: Op,
+    left: i64,
+    right: i64,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let name = op.name();
+    let simulation = client
+        .simulate_transaction(transaction)
+        .inspect_err(|err| eprintln!("Simulating {} ({} , {}) failed: {}", name, left, right, err))?
+        .value;
+
+    let result = match simulation.err {
+        Some(_) => None,
+        None => decode_result(simulation.return_data, simulation.logs.clone())?,
+    };
+    let logs = simulation.logs.unwrap_or_default();
+
+    if json {
+        let mut output = operation_json(op, left, right, result.as_deref(), None);
+        output["error"] = json!(simulation.err.map(|err| err.to_string()));
+        output["logs"] = json!(logs);
+        println!("{}", output);
+        return Ok(());
+    }
+
+    let result = match (simulation.err, result) {
+        (Some(err), _) => format!("would fail: {}", err),
+        (None, Some(result)) => format_result(op, &result),
+        (None, None) => String::from("unknown"),
+    };
+    println!(
+        "{:<16} {:<24} {} (simulated)",
+        name,
+        format_inputs(op.opcode(), left, right),
+        result
+    );
+    for line in logs {
+        println!("    {}", line);
+        if let Some(event) = decode_event(&line) {
+            println!("    Event: {:?}", event);
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the computed result of a confirmed transaction from its return data,
+/// falling back to the `CalcEvent` the program logged
+fn fetch_result(
+    client: &RpcClient,
+    signature: &Signature,
+) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let transaction = client
+        .get_transaction(signature, UiTransactionEncoding::Json)
+        .inspect_err(|err| eprintln!("Fetching transaction {} failed: {}", signature, err))?;
+    let Some(meta) = transaction.transaction.meta else {
+        return Ok(None);
+    };
+
+    decode_result(meta.return_data.into(), meta.log_messages.into())
+}
+
+/// Recover the raw result bytes from return data when the program set any,
+/// otherwise from the `CalcEvent` it logged
+fn decode_result(
+    return_data: Option<UiTransactionReturnData>,
+    logs: Option<Vec<String>>,
+) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    if let Some(return_data) = return_data {
+        return Ok(Some(STANDARD.decode(&return_data.data.0)?));
+    }
+
+    let result = logs
+        .unwrap_or_default()
+        .iter()
+        .find_map(|line| decode_event(line))
+        .map(|event| event.result.to_le_bytes().to_vec());
+    Ok(result)
+}
+
+/// Decode a `CalcEvent` from a `Program data:` log line, whose fields are
+/// base64-encoded and space separated
+fn decode_event(line: &str) -> Option<CalcEvent> {
+    line.strip_prefix("Program data: ")?
+        .split(' ')
+        .find_map(|field| {
+            let bytes = STANDARD.decode(field).ok()?;
+            CalcEvent::try_from_slice(&bytes).ok()
+        })
+}
+
+/// One operation as a JSON object, e.g.
+/// `{"op":"add","a":15,"b":7,"result":22,"signature":"..."}`. The result is
+/// null when it could not be read back.
+fn operation_json(
+    op: Op,
+    left: i64,
+    right: i64,
+    result: Option<&[u8]>,
+    signature: Option<&Signature>,
+) -> Value {
+    json!({
+        "op": op.flag_name(),
+        "a": left,
+        "b": right,
+        "result": result.map_or(Value::Null, |bytes| result_json(op, bytes)),
+        "signature": signature.map(Signature::to_string),
+    })
+}
+
+/// Render the inputs of an operation the way it reads on paper, e.g. `15 + 7`
+fn format_inputs(operation: u8, left: i64, right: i64) -> String {
+    let symbol = match operation {
+        0 => "+",
+        1 => "-",
+        2 => "*",
+        3 => "/",
+        4 => "%",
+        5 => "^",
+        6 => "&",
+        7 => "|",
+        8 => "xor",
+        9 => "<<",
+        10 => ">>",
+        11 => "==",
+        12 => "<",
+        13 => ">",
+        14 => "!=",
+        15 => return format!("sqrt({})", left),
+        16 => return format!("|{}|", left),
+        17 => return format!("-({})", left),
+        18 => return format!("gcd({}, {})", left, right),
+        19 => return format!("lcm({}, {})", left, right),
+        20 => "divmod",
+        _ => "?",
+    };
+    format!("{} {} {}", left, symbol, right)
+}
+
+/// Restore return data to the width `op` produces. The RPC trims trailing
+/// zero bytes, so `42` from an add arrives as `[42]` and is padded back out,
+/// while the 8-byte event fallback for a comparison is cut down to its flag.
+/// `None` if the bytes can't have come from `op`.
+fn pad_result(op: Op, bytes: &[u8]) -> Option<Vec<u8>> {
+    let width = op.result_len();
+    let (kept, extra) = bytes.split_at(bytes.len().min(width));
+    if extra.iter().any(|&byte| byte != 0) {
+        return None;
+    }
+    let mut padded = kept.to_vec();
+    padded.resize(width, 0);
+    Some(padded)
+}
+
+/// Decode one little-endian i64 from exactly 8 bytes
+fn read_i64(bytes: &[u8]) -> i64 {
+    i64::from_le_bytes(bytes.try_into().expect("Result slices are 8 bytes"))
+}
+
+/// Render calculator return data for `op`: a bool for a comparison, a
+/// quotient and remainder for divmod and a number otherwise
+fn format_result(op: Op, bytes: &[u8]) -> String {
+    let Some(padded) = pad_result(op, bytes) else {
+        return format!("unexpected return data {:?}", bytes);
+    };
+    match op {
+        Op::Eq | Op::Lt | Op::Gt | Op::Ne => (padded[0] == 1).to_string(),
+        Op::Divmod => format!(
+            "{} remainder {}",
+            read_i64(&padded[..8]),
+            read_i64(&padded[8..])
+        ),
+        _ => read_i64(&padded).to_string(),
+    }
+}
+
+/// Calculator return data for `op` as a JSON value: a number for a result, a
+/// bool for a comparison, and an object for a quotient and remainder
+fn result_json(op: Op, bytes: &[u8]) -> Value {
+    let Some(padded) = pad_result(op, bytes) else {
+        return Value::Null;
+    };
+    match op {
+        Op::Eq | Op::Lt | Op::Gt | Op::Ne => Value::from(padded[0] == 1),
+        Op::Divmod => json!({
+            "quotient": read_i64(&padded[..8]),
+            "remainder": read_i64(&padded[8..]),
+        }),
+        _ => Value::from(read_i64(&padded)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_local_url_preset() {
+        let args = Args::try_parse_from(["client"]).expect("Defaults should parse");
+        assert_eq!(args.rpc_url(), "https://api.devnet.solana.com");
+
+        let args = Args::try_parse_from(["client", "--local"]).expect("--local should parse");
+        assert_eq!(args.rpc_url(), LOCAL_URL);
+
+        // A preset and an explicit endpoint contradict each other
+        assert!(Args::try_parse_from(["client", "--local", "--url", "http://x"]).is_err());
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let event = CalcEvent {
+            op: 0,
+            a: 15,
+            b: 7,
+            result: 22,
+        };
+        let line = format!(
+            "Program data: {}",
+            STANDARD.encode(borsh::to_vec(&event).unwrap())
+        );
+        assert_eq!(decode_event(&line), Some(event));
+        assert_eq!(decode_event("Program log: Result = 22"), None);
+    }
+
+    #[test]
+    fn test_operation_json() {
+        let signature = Signature::default();
+        let output = operation_json(Op::Add, 15, 7, Some(&22i64.to_le_bytes()), Some(&signature));
+        assert_eq!(
+            output.to_string(),
+            format!(
+                r#"{{"op":"add","a":15,"b":7,"result":22,"signature":"{}"}}"#,
+                signature
+            )
+        );
+
+        let comparison = operation_json(Op::Lt, 3, 9, Some(&[1]), None);
+        assert_eq!(comparison["result"], json!(true));
+        assert_eq!(comparison["signature"], Value::Null);
+
+        let mut divmod = 3i64.to_le_bytes().to_vec();
+        divmod.extend_from_slice(&2i64.to_le_bytes());
+        let divmod = operation_json(Op::Divmod, 17, 5, Some(&divmod), None);
+        assert_eq!(divmod["op"], json!("divmod"));
+        assert_eq!(divmod["result"], json!({ "quotient": 3, "remainder": 2 }));
+
+        let unknown = operation_json(Op::Shl, 1, 10, None, None);
+        assert_eq!(unknown["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_trimmed_return_data() {
+        // The RPC drops trailing zero bytes, so widths come from the opcode
+        assert_eq!(format_result(Op::Add, &[42]), "42");
+        assert_eq!(result_json(Op::Add, &[42]), json!(42));
+        assert_eq!(format_result(Op::Lt, &[]), "false");
+        assert_eq!(format_result(Op::Divmod, &[3]), "3 remainder 0");
+        assert_eq!(
+            result_json(Op::Divmod, &[3]),
+            json!({ "quotient": 3, "remainder": 0 })
+        );
+
+        // The 8-byte event fallback still decodes as a comparison
+        assert_eq!(format_result(Op::Eq, &1i64.to_le_bytes()), "true");
+        assert_eq!(
+            format_result(Op::Eq, &[1, 1]),
+            "unexpected return data [1, 1]"
+        );
+        let too_wide = [0, 0, 0, 0, 0, 0, 0, 0, 1];
+        assert_eq!(result_json(Op::Add, &too_wide), Value::Null);
+    }
+}
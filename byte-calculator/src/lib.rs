@@ -1,72 +1,1899 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use first_solana_math::{checked_op, checked_op_unsigned, modpow, MathError, MAX_EXPONENT};
 use solana_program::{
-    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, msg, program_error::ProgramError, pubkey::Pubkey,
+    account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable, entrypoint,
+    entrypoint::ProgramResult,
+    log::sol_log_data,
+    msg,
+    program::{invoke_signed, set_return_data},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{rent::Rent, Sysvar},
 };
 
 entrypoint!(process_instruction);
 
 pub fn process_instruction(
-    _program_id: &Pubkey,
-    _accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    
-    if instruction_data.len() < 3 {
-        msg!("Need opcode + 2 operands");
-        return Ok(());
+    if instruction_data.first() == Some(&CONFIG_MODE) {
+        return process_set_config(program_id, accounts, &instruction_data[1..]);
+    }
+
+    // A config account ahead of the result account limits the opcodes
+    let (enabled_ops, accounts) = split_config(program_id, accounts)?;
+
+    // Unary opcodes take a single operand, modpow takes three, list opcodes
+    // take any number, RPN mode takes a token stream, and unsigned mode takes
+    // a binary operation on u64 operands
+    if let Some(&operation) = instruction_data.first() {
+        check_enabled(enabled_ops, operation)?;
+        if UNARY_OPCODES.contains(&operation) {
+            return process_unary(program_id, accounts, operation, instruction_data);
+        }
+        if operation == MODPOW_OPCODE {
+            return process_modpow(program_id, accounts, instruction_data);
+        }
+        if LIST_OPCODES.contains(&operation) {
+            return process_list(program_id, accounts, operation, &instruction_data[1..]);
+        }
+        if operation == RPN_MODE {
+            return process_rpn(program_id, accounts, enabled_ops, &instruction_data[1..]);
+        }
+        if operation == UNSIGNED_MODE {
+            return process_unsigned(program_id, accounts, enabled_ops, &instruction_data[1..]);
+        }
+    }
+
+    // Opcode followed by two little-endian i64 operands
+    if instruction_data.len() < 17 {
+        msg!("Need opcode + 2 i64 operands (17 bytes), got {} bytes", instruction_data.len());
+        return Err(ProgramError::InvalidInstructionData);
     }
 
     let operation = instruction_data[0];
-    let left = instruction_data[1] as i64;
-    let right = instruction_data[2] as i64;
-
-    let result = match operation {
-        0 => {
-            msg!("Addition: {} + {}", left, right);
-            left + right
-        },
-        1 => {
-            msg!("Subtraction: {} - {}", left, right);
-            left - right
-        },
-        2 => {
-            msg!("Multiplication: {} * {}", left, right);
-            left * right
-        },
-        3 => {
-            msg!("Division: {} / {}", left, right);
-            if right != 0 {
-                left / right
-            } else {
-                msg!("Division by zero is not allowed");
-                return Err(ProgramError::InvalidInstructionData);
-            }
-        },
-        4 => {
-            msg!("Modulus: {} % {}", left, right);
-            if right != 0 {
-                left % right
-            } else {
-                msg!("Modulus by zero is not allowed");
-                return Err(ProgramError::InvalidInstructionData);
-            }
-        },
-        5 => {
-            msg!("Power: {} ^ {}", left, right);
-            if right >= 0 {
-            left.pow(right as u32)               
-            } else {
-                msg!("Negative exponent is not allowed");
-                return Err(ProgramError::InvalidInstructionData);
-            }
+    let left = read_operand(&instruction_data[1..9])?;
+    let right = read_operand(&instruction_data[9..17])?;
+
+    if operation == DIVMOD_OPCODE {
+        return process_divmod(program_id, accounts, left, right);
+    }
+
+    let result = evaluate(operation, left, right)?;
+
+    msg!("Result = {}", result);
+    store_result(program_id, accounts, operation, left, right, result)?;
+    CalcEvent {
+        op: operation,
+        a: left,
+        b: right,
+        result,
+    }
+    .emit()?;
+
+    if COMPARISON_OPCODES.contains(&operation) {
+        // Comparisons answer with a single byte: 1 for true, 0 for false
+        set_return_data(&[result as u8]);
+    } else {
+        // Hand the result back to the caller as a little-endian i64
+        set_return_data(&result.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Evaluate a single-result opcode without logging or touching accounts.
+/// Unary opcodes only read `left`, and comparisons yield 1 for true and 0 for
+/// false. Divmod returns two values, so it is not an opcode here.
+pub fn compute(operation: u8, left: i64, right: i64) -> Result<i64, ProgramError> {
+    checked_op(operation, left, right).map_err(math_error)
+}
+
+/// Map a shared math error onto the error the calculator reports for it
+fn math_error(error: MathError) -> ProgramError {
+    match error {
+        MathError::Overflow => ProgramError::ArithmeticOverflow,
+        MathError::InvalidOperand => ProgramError::InvalidInstructionData,
+        MathError::UnknownOpcode => CalcError::UnknownOpcode.into(),
+    }
+}
+
+/// Run `compute`, logging the operation first and the reason if it fails
+fn evaluate(operation: u8, left: i64, right: i64) -> Result<i64, ProgramError> {
+    match operation {
+        0 => msg!("Addition: {} + {}", left, right),
+        1 => msg!("Subtraction: {} - {}", left, right),
+        2 => msg!("Multiplication: {} * {}", left, right),
+        3 => msg!("Division: {} / {}", left, right),
+        4 => msg!("Modulus: {} % {}", left, right),
+        5 => msg!("Power: {} ^ {}", left, right),
+        6 => msg!("Bitwise AND: {} & {}", left, right),
+        7 => msg!("Bitwise OR: {} | {}", left, right),
+        8 => msg!("Bitwise XOR: {} ^ {}", left, right),
+        9 => msg!("Left shift: {} << {}", left, right),
+        10 => msg!("Right shift: {} >> {}", left, right),
+        11 => msg!("Comparison: {} == {}", left, right),
+        12 => msg!("Comparison: {} < {}", left, right),
+        13 => msg!("Comparison: {} > {}", left, right),
+        14 => msg!("Comparison: {} != {}", left, right),
+        15 => msg!("Square root: sqrt({})", left),
+        16 => msg!("Absolute value: |{}|", left),
+        17 => msg!("Negation: -({})", left),
+        18 => msg!("GCD: gcd({}, {})", left, right),
+        19 => msg!("LCM: lcm({}, {})", left, right),
+        FACTORIAL_OPCODE => msg!("Factorial: {}!", left),
+        FIBONACCI_OPCODE => msg!("Fibonacci: fib({})", left),
+        BPS_OPCODE => msg!("Basis points: {} * {} / 10000", left, right),
+        _ => {},
+    }
+
+    compute(operation, left, right).inspect_err(|error| match (operation, error) {
+        (5, ProgramError::ArithmeticOverflow) if right > MAX_EXPONENT => msg!(
+            "Exponent {} is above the ceiling of {}",
+            right,
+            MAX_EXPONENT
+        ),
+        (_, ProgramError::ArithmeticOverflow) => msg!("overflow"),
+        (3, _) => msg!("Division by zero is not allowed"),
+        (4, _) => msg!("Modulus by zero is not allowed"),
+        (5, _) => msg!("Negative exponent is not allowed"),
+        (9 | 10, _) => msg!("Shift amount must be in 0..64"),
+        (15, _) => msg!("Square root of a negative number is not allowed"),
+        (FACTORIAL_OPCODE, _) => msg!("Factorial of a negative number is not allowed"),
+        (FIBONACCI_OPCODE, _) => msg!("Fibonacci of a negative index is not allowed"),
+        (BPS_OPCODE, _) => msg!("Negative basis points are not allowed"),
+        _ => msg!(
+            "Unknown operation {} with operands {} and {}, expected 0..={}, {} for RPN, \
+             {} for unsigned or {} for config",
+            operation,
+            left,
+            right,
+            BPS_OPCODE,
+            RPN_MODE,
+            UNSIGNED_MODE,
+            CONFIG_MODE
+        ),
+    })
+}
+
+/// Comparison opcodes, which return a single byte instead of an i64
+const COMPARISON_OPCODES: std::ops::RangeInclusive<u8> = 11..=14;
+
+/// Opcode dividing with remainder, which returns two i64 values
+const DIVMOD_OPCODE: u8 = 20;
+
+/// Opcode raising a base to a power modulo a third operand
+const MODPOW_OPCODE: u8 = 21;
+
+/// Opcode adding up a list of operands
+const SUM_ALL_OPCODE: u8 = 22;
+
+/// Opcode multiplying a list of operands
+const PRODUCT_ALL_OPCODE: u8 = 23;
+
+/// Opcodes that fold a length-prefixed list of operands
+const LIST_OPCODES: std::ops::RangeInclusive<u8> = SUM_ALL_OPCODE..=PRODUCT_ALL_OPCODE;
+
+/// Opcode computing the factorial of one operand
+const FACTORIAL_OPCODE: u8 = 24;
+
+/// Opcode computing the Fibonacci number at one operand
+const FIBONACCI_OPCODE: u8 = 25;
+
+/// Opcode taking `right` basis points of `left`, i.e. `left * right / 10000`
+const BPS_OPCODE: u8 = 26;
+
+/// Opcodes that read one operand: integer square root, absolute value,
+/// negation, factorial and Fibonacci
+const UNARY_OPCODES: &[u8] = &[15, 16, 17, FACTORIAL_OPCODE, FIBONACCI_OPCODE];
+
+/// Evaluate a unary opcode followed by one little-endian i64 operand
+fn process_unary(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    operation: u8,
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if instruction_data.len() < 9 {
+        msg!("Need opcode + 1 i64 operand (9 bytes), got {} bytes", instruction_data.len());
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let operand = read_operand(&instruction_data[1..9])?;
+    let result = evaluate(operation, operand, 0)?;
+
+    msg!("Result = {}", result);
+    store_result(program_id, accounts, operation, operand, 0, result)?;
+    CalcEvent {
+        op: operation,
+        a: operand,
+        b: 0,
+        result,
+    }
+    .emit()?;
+
+    set_return_data(&result.to_le_bytes());
+    Ok(())
+}
+
+/// Divide and return both the quotient and remainder, concatenated as two
+/// little-endian i64 values, so they always come from the same operands
+fn process_divmod(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    left: i64,
+    right: i64,
+) -> ProgramResult {
+    msg!("Division with remainder: {} / {}", left, right);
+    if right == 0 {
+        msg!("Division by zero is not allowed");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let (Some(quotient), Some(remainder)) = (left.checked_div(right), left.checked_rem(right)) else {
+        msg!("overflow");
+        return Err(ProgramError::ArithmeticOverflow);
+    };
+
+    msg!("Result = {} remainder {}", quotient, remainder);
+    store_result(program_id, accounts, DIVMOD_OPCODE, left, right, quotient)?;
+    CalcEvent {
+        op: DIVMOD_OPCODE,
+        a: left,
+        b: right,
+        result: quotient,
+    }
+    .emit()?;
+
+    set_return_data(&[quotient.to_le_bytes(), remainder.to_le_bytes()].concat());
+    Ok(())
+}
+
+/// Compute `(base ^ exponent) mod modulus` from an opcode followed by three
+/// little-endian i64 operands, returning the result as a little-endian i64
+fn process_modpow(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if instruction_data.len() < 25 {
+        msg!(
+            "Need opcode + 3 i64 operands (25 bytes), got {} bytes",
+            instruction_data.len()
+        );
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let base = read_operand(&instruction_data[1..9])?;
+    let exponent = read_operand(&instruction_data[9..17])?;
+    let modulus = read_operand(&instruction_data[17..25])?;
+
+    msg!("Modular power: {} ^ {} mod {}", base, exponent, modulus);
+    let result = modpow(base, exponent, modulus)
+        .inspect_err(|error| match error {
+            MathError::Overflow => msg!("overflow"),
+            _ if modulus == 0 => msg!("Modulus of zero is not allowed"),
+            _ => msg!("Negative exponent is not allowed"),
+        })
+        .map_err(math_error)?;
+
+    msg!("Result = {}", result);
+    store_result(program_id, accounts, MODPOW_OPCODE, base, exponent, result)?;
+    CalcEvent {
+        op: MODPOW_OPCODE,
+        a: base,
+        b: exponent,
+        result,
+    }
+    .emit()?;
+
+    set_return_data(&result.to_le_bytes());
+    Ok(())
+}
+
+/// Fold a list of operands with `checked_add` or `checked_mul`
+///
+/// The list is a little-endian u32 count followed by that many little-endian
+/// i64 operands, and must fill the rest of the instruction exactly. An empty
+/// list sums to 0 and multiplies to 1.
+fn process_list(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    operation: u8,
+    list: &[u8],
+) -> ProgramResult {
+    let Some((count, operands)) = list.split_first_chunk::<4>() else {
+        msg!("Need a 4-byte operand count, got {} bytes", list.len());
+        return Err(ProgramError::InvalidInstructionData);
+    };
+    let count = u32::from_le_bytes(*count) as usize;
+    if operands.len() != count.saturating_mul(8) {
+        msg!(
+            "Operand count {} needs {} bytes, got {} bytes",
+            count,
+            count.saturating_mul(8),
+            operands.len()
+        );
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let mut values = operands.chunks_exact(8).map(read_operand);
+    let result = if operation == SUM_ALL_OPCODE {
+        msg!("Sum of {} operands", count);
+        values.try_fold(0i64, |sum, value| {
+            sum.checked_add(value?)
+                .ok_or(ProgramError::ArithmeticOverflow)
+        })
+    } else {
+        msg!("Product of {} operands", count);
+        values.try_fold(1i64, |product, value| {
+            product
+                .checked_mul(value?)
+                .ok_or(ProgramError::ArithmeticOverflow)
+        })
+    }
+    .inspect_err(|_| msg!("overflow"))?;
+
+    msg!("Result = {}", result);
+    store_result(program_id, accounts, operation, 0, 0, result)?;
+    CalcEvent {
+        op: operation,
+        a: 0,
+        b: 0,
+        result,
+    }
+    .emit()?;
+
+    set_return_data(&result.to_le_bytes());
+    Ok(())
+}
+
+/// Mode byte selecting the stack (RPN) evaluator
+pub const RPN_MODE: u8 = 0xFF;
+
+/// RPN token pushing the following little-endian i64 onto the stack
+pub const RPN_PUSH: u8 = 0;
+
+/// RPN token applying the following binary opcode to the top two values
+pub const RPN_APPLY: u8 = 1;
 
-        },
-        _ =>{
-            msg!("Unknown operation: {}", operation);
-            return Err(ProgramError::InvalidInstructionData);
+/// Evaluate a postfix expression, e.g. `3 4 + 5 *`, and return the single
+/// value left on the stack
+///
+/// Each token is either `RPN_PUSH` followed by an 8-byte operand or
+/// `RPN_APPLY` followed by one binary opcode, which pops the right then the
+/// left operand and pushes the result.
+///
+/// Every applied opcode must be enabled in `enabled_ops`.
+fn process_rpn(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    enabled_ops: u64,
+    tokens: &[u8],
+) -> ProgramResult {
+    let mut stack: Vec<i64> = Vec::new();
+    let mut rest = tokens;
+
+    while let Some((&token, tail)) = rest.split_first() {
+        match token {
+            RPN_PUSH => {
+                if tail.len() < 8 {
+                    msg!("RPN push needs an 8-byte operand");
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                stack.push(read_operand(&tail[..8])?);
+                rest = &tail[8..];
+            },
+            RPN_APPLY => {
+                let Some((&operation, tail)) = tail.split_first() else {
+                    msg!("RPN apply needs an opcode");
+                    return Err(ProgramError::InvalidInstructionData);
+                };
+                if UNARY_OPCODES.contains(&operation) {
+                    msg!("RPN only supports binary opcodes, got {}", operation);
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                check_enabled(enabled_ops, operation)?;
 
+                let (Some(right), Some(left)) = (stack.pop(), stack.pop()) else {
+                    msg!("RPN stack underflow applying opcode {}", operation);
+                    return Err(ProgramError::InvalidInstructionData);
+                };
+                stack.push(evaluate(operation, left, right)?);
+                rest = tail;
+            },
+            _ => {
+                msg!("Unknown RPN token: {}", token);
+                return Err(ProgramError::InvalidInstructionData);
+            },
         }
+    }
+
+    let [result] = stack[..] else {
+        msg!("RPN expression must leave exactly one value, found {}", stack.len());
+        return Err(ProgramError::InvalidInstructionData);
     };
 
     msg!("Result = {}", result);
+    store_result(program_id, accounts, RPN_MODE, 0, 0, result)?;
+    CalcEvent {
+        op: RPN_MODE,
+        a: 0,
+        b: 0,
+        result,
+    }
+    .emit()?;
+
+    set_return_data(&result.to_le_bytes());
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Mode byte selecting unsigned arithmetic for one binary operation
+///
+/// Without it operands are signed i64s. In unsigned mode they are u64s, so a
+/// subtraction that would go negative fails with an overflow where signed
+/// mode produces the negative result, e.g. `5 - 8` is an error here but -3
+/// otherwise.
+pub const UNSIGNED_MODE: u8 = 0xFE;
+
+/// Apply a binary opcode to two little-endian u64 operands and return the
+/// result as a little-endian u64
+///
+/// The instruction data after the mode byte has the signed layout: opcode
+/// followed by two 8-byte operands. The result account and event store the
+/// same bits as i64s.
+fn process_unsigned(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    enabled_ops: u64,
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if instruction_data.len() < 17 {
+        msg!(
+            "Unsigned mode needs opcode + 2 u64 operands (17 bytes), got {} bytes",
+            instruction_data.len()
+        );
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let operation = instruction_data[0];
+    check_enabled(enabled_ops, operation)?;
+    let left = read_operand(&instruction_data[1..9])? as u64;
+    let right = read_operand(&instruction_data[9..17])? as u64;
+
+    msg!("Unsigned operation {}: {} and {}", operation, left, right);
+    let result = checked_op_unsigned(operation, left, right)
+        .map_err(math_error)
+        .inspect_err(|error| match (operation, error) {
+            (1, ProgramError::ArithmeticOverflow) => msg!(
+                "Unsigned subtraction {} - {} would go negative",
+                left,
+                right
+            ),
+            (5, ProgramError::ArithmeticOverflow) if right > MAX_EXPONENT as u64 => msg!(
+                "Exponent {} is above the ceiling of {}",
+                right,
+                MAX_EXPONENT
+            ),
+            (_, ProgramError::ArithmeticOverflow) => msg!("overflow"),
+            (3 | 4, _) => msg!("Division by zero is not allowed"),
+            (9 | 10, _) => msg!("Shift amount must be in 0..64"),
+            _ => msg!(
+                "Operation {} has no unsigned form, expected 0..=14, 18 or 19",
+                operation
+            ),
+        })?;
+
+    msg!("Result = {}", result);
+    store_result(
+        program_id,
+        accounts,
+        operation,
+        left as i64,
+        right as i64,
+        result as i64,
+    )?;
+    CalcEvent {
+        op: operation,
+        a: left as i64,
+        b: right as i64,
+        result: result as i64,
+    }
+    .emit()?;
+
+    set_return_data(&result.to_le_bytes());
+    Ok(())
+}
+
+/// Mode byte setting the config account's enabled opcodes, followed by the
+/// little-endian u64 mask
+pub const CONFIG_MODE: u8 = 0xFD;
+
+/// Seed for the config PDA
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Derive the config PDA address and bump
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// Which opcodes the calculator accepts, stored in the config PDA
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct CalcConfig {
+    /// Key allowed to change the mask
+    pub admin: Pubkey,
+
+    /// Bit `n` enables opcode `n`. Opcodes of 64 and above, such as the mode
+    /// bytes, are never gated.
+    pub enabled_ops: u64,
+
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl CalcConfig {
+    /// Serialized size in bytes: admin (32) + enabled_ops (8) + bump (1)
+    pub const LEN: usize = 32 + 8 + 1;
+}
+
+/// Mask enabling every opcode, used when no config account is passed
+const ALL_OPS: u64 = u64::MAX;
+
+/// Load the config held by `account`, or `None` if it is not the config. The
+/// config is recognised as a program-owned account of `CalcConfig::LEN`
+/// bytes at the PDA for its stored bump.
+fn load_config(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<Option<CalcConfig>, ProgramError> {
+    if account.owner != program_id || account.data_len() != CalcConfig::LEN {
+        return Ok(None);
+    }
+
+    let config = CalcConfig::try_from_slice(&account.data.borrow())?;
+    let is_config = Pubkey::create_program_address(&[CONFIG_SEED, &[config.bump]], program_id)
+        .is_ok_and(|address| address == *account.key);
+    Ok(is_config.then_some(config))
+}
+
+/// Split off a leading config account and return its mask along with the
+/// remaining accounts, so a result account can still come first on its own.
+fn split_config<'a, 'b>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'b>],
+) -> Result<(u64, &'a [AccountInfo<'b>]), ProgramError> {
+    let Some((config_account, rest)) = accounts.split_first() else {
+        return Ok((ALL_OPS, accounts));
+    };
+    match load_config(program_id, config_account)? {
+        Some(config) => Ok((config.enabled_ops, rest)),
+        None => Ok((ALL_OPS, accounts)),
+    }
+}
+
+/// Reject an opcode whose bit is clear in the mask
+fn check_enabled(enabled_ops: u64, operation: u8) -> ProgramResult {
+    let enabled = match 1u64.checked_shl(operation as u32) {
+        Some(bit) => enabled_ops & bit != 0,
+        None => true,
+    };
+    if !enabled {
+        msg!("Opcode {} is disabled by the config account", operation);
+        return Err(CalcError::OperationDisabled.into());
+    }
+    Ok(())
+}
+
+/// Bytes of program data metadata: tag (4) + slot (8) + authority flag (1) +
+/// authority (32)
+const PROGRAM_DATA_HEADER_LEN: usize = 4 + 8 + 1 + 32;
+
+/// Read the upgrade authority out of the calculator's program data account,
+/// `None` once the program has been made immutable
+fn upgrade_authority(
+    program_id: &Pubkey,
+    program_data_account: &AccountInfo,
+) -> Result<Option<Pubkey>, ProgramError> {
+    let expected_address = bpf_loader_upgradeable::get_program_data_address(program_id);
+    if *program_data_account.key != expected_address {
+        msg!(
+            "Expected program data account {}, got {}",
+            expected_address,
+            program_data_account.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *program_data_account.owner != bpf_loader_upgradeable::id() {
+        msg!("Program data account must be owned by the upgradeable loader");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // ProgramData layout: u32 tag (3), u64 slot, then an optional authority
+    // as a one-byte flag followed by the key
+    let data = program_data_account.data.borrow();
+    if data.len() < PROGRAM_DATA_HEADER_LEN || data[..4] != 3u32.to_le_bytes() {
+        msg!("Program data account does not hold program data");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if data[12] == 0 {
+        return Ok(None);
+    }
+    let authority = Pubkey::try_from(&data[13..PROGRAM_DATA_HEADER_LEN])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok(Some(authority))
+}
+
+/// Set the enabled opcode mask. The config PDA is created on first use, and
+/// only the program's upgrade authority may create it, becoming its admin so
+/// nobody can claim the config ahead of the deployer. Afterwards only that
+/// admin can change the mask.
+///
+/// Accounts expected:
+/// 1. `[writable]` Config PDA
+/// 2. `[signer, writable]` Admin, who pays for the account on creation
+/// 3. `[]` System Program
+/// 4. `[]` The calculator's program data account, needed on creation only
+fn process_set_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let Ok(mask) = <[u8; 8]>::try_from(instruction_data) else {
+        msg!(
+            "Config mode needs a u64 mask (8 bytes), got {} bytes",
+            instruction_data.len()
+        );
+        return Err(ProgramError::InvalidInstructionData);
+    };
+    let enabled_ops = u64::from_le_bytes(mask);
+
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin_account = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    if !admin_account.is_signer {
+        msg!("Config admin must sign");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let (expected_address, bump) = find_config_address(program_id);
+    if *config_account.key != expected_address {
+        msg!("Config account does not match the derived PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if config_account.data_is_empty() {
+        if *system_program_account.key != system_program::id() {
+            msg!(
+                "Expected the system program, got {}",
+                system_program_account.key
+            );
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let program_data_account = next_account_info(accounts_iter)?;
+        if upgrade_authority(program_id, program_data_account)? != Some(*admin_account.key) {
+            msg!(
+                "Only the upgrade authority can create the config, not {}",
+                admin_account.key
+            );
+            return Err(CalcError::Unauthorized.into());
+        }
+
+        let required_lamports = Rent::get()?.minimum_balance(CalcConfig::LEN);
+        invoke_signed(
+            &system_instruction::create_account(
+                admin_account.key,
+                config_account.key,
+                required_lamports,
+                CalcConfig::LEN as u64,
+                program_id,
+            ),
+            &[
+                admin_account.clone(),
+                config_account.clone(),
+                system_program_account.clone(),
+            ],
+            &[&[CONFIG_SEED, &[bump]]],
+        )?;
+        msg!(
+            "Created config {} with admin {}",
+            config_account.key,
+            admin_account.key
+        );
+    } else {
+        if config_account.owner != program_id {
+            msg!("Config account must be owned by the calculator");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let config = CalcConfig::try_from_slice(&config_account.data.borrow())?;
+        if config.admin != *admin_account.key {
+            msg!("Only the config admin {} can set the mask", config.admin);
+            return Err(CalcError::Unauthorized.into());
+        }
+    }
+
+    let config = CalcConfig {
+        admin: *admin_account.key,
+        enabled_ops,
+        bump,
+    };
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+
+    msg!("Enabled opcodes: {:#018x}", enabled_ops);
+    Ok(())
+}
+
+/// Errors specific to the calculator, surfaced as `ProgramError::Custom` with
+/// the variant's discriminant as the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalcError {
+    /// The opcode does not name any calculator operation
+    UnknownOpcode,
+
+    /// The config account has the opcode's bit cleared
+    OperationDisabled,
+
+    /// The signer is not allowed to create or change the config
+    Unauthorized,
+}
+
+impl From<CalcError> for ProgramError {
+    fn from(error: CalcError) -> Self {
+        ProgramError::Custom(error as u32)
+    }
+}
+
+/// The last computation, stored in the optional result account
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct CalcResult {
+    /// Opcode of the operation, or `RPN_MODE` for an expression
+    pub last_op: u8,
+
+    /// Left operand, 0 for expressions
+    pub operand_a: i64,
+
+    /// Right operand, 0 for unary operations and expressions
+    pub operand_b: i64,
+
+    /// Computed result (1 or 0 for comparisons, the quotient for divmod)
+    pub result: i64,
+}
+
+impl CalcResult {
+    /// Serialized size in bytes: last_op (1) + operand_a (8) + operand_b (8) +
+    /// result (8)
+    pub const LEN: usize = 1 + 8 + 8 + 8;
+}
+
+/// A completed computation, logged as a Borsh-serialized `Program data:` line
+/// alongside the human-readable messages so indexers need not parse strings
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct CalcEvent {
+    /// Opcode of the operation, or `RPN_MODE` for an expression
+    pub op: u8,
+
+    /// Left operand (the base for modpow), 0 for list opcodes and expressions
+    pub a: i64,
+
+    /// Right operand (the exponent for modpow), 0 for unary operations, list
+    /// opcodes and expressions
+    pub b: i64,
+
+    /// Computed result (1 or 0 for comparisons, the quotient for divmod)
+    pub result: i64,
+}
+
+impl CalcEvent {
+    /// Log the Borsh-serialized event as a `Program data:` line
+    fn emit(&self) -> ProgramResult {
+        sol_log_data(&[&borsh::to_vec(self)?]);
+        Ok(())
+    }
+}
+
+/// Write the computation into the result account, if one was passed as the
+/// first account. Without accounts the calculator only logs.
+fn store_result(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    last_op: u8,
+    operand_a: i64,
+    operand_b: i64,
+    result: i64,
+) -> ProgramResult {
+    let Some(result_account) = accounts.first() else {
+        return Ok(());
+    };
+
+    if result_account.owner != program_id {
+        msg!("Result account must be owned by the calculator");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !result_account.is_writable {
+        msg!("Result account must be writable");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if result_account.data_len() < CalcResult::LEN {
+        msg!("Result account needs at least {} bytes", CalcResult::LEN);
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    // The config is big enough to pass for a result account, but writing a
+    // result over it would replace the admin and mask
+    if load_config(program_id, result_account)?.is_some() {
+        msg!("The config account cannot hold a result");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let calc_result = CalcResult {
+        last_op,
+        operand_a,
+        operand_b,
+        result,
+    };
+    calc_result.serialize(&mut &mut result_account.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Decode one 8-byte little-endian operand
+fn read_operand(bytes: &[u8]) -> Result<i64, ProgramError> {
+    let bytes: [u8; 8] = bytes
+        .try_into()
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    Ok(i64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use litesvm::{types::TransactionMetadata, LiteSVM};
+    use proptest::prelude::*;
+    use solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+    use solana_sdk::{
+        account::{Account, ReadableAccount},
+        instruction::{AccountMeta, Instruction, InstructionError},
+        message::Message,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    };
+    use std::{cell::RefCell, sync::Once};
+
+    thread_local! {
+        static RETURN_DATA: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+        static LOG_DATA: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Syscall stubs that keep the return data and `sol_log_data` fields
+    /// set by the current test thread
+    struct ReturnDataStubs;
+
+    impl SyscallStubs for ReturnDataStubs {
+        fn sol_set_return_data(&self, data: &[u8]) {
+            RETURN_DATA.with(|return_data| *return_data.borrow_mut() = data.to_vec());
+        }
+
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            LOG_DATA.with(|log_data| {
+                let mut log_data = log_data.borrow_mut();
+                log_data.extend(fields.iter().map(|field| field.to_vec()));
+            });
+        }
+    }
+
+    /// Run one instruction natively and hand back whatever it set as return
+    /// data
+    fn run(operation: u8, left: i64, right: i64) -> Result<Vec<u8>, ProgramError> {
+        run_bytes(&encode(operation, left, right))
+    }
+
+    /// Run raw instruction data natively and hand back its return data
+    fn run_bytes(instruction_data: &[u8]) -> Result<Vec<u8>, ProgramError> {
+        run_with_accounts(&[], instruction_data)
+    }
+
+    /// Run raw instruction data natively against the given accounts
+    fn run_with_accounts(
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> Result<Vec<u8>, ProgramError> {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(ReturnDataStubs));
+        });
+
+        RETURN_DATA.with(|return_data| return_data.borrow_mut().clear());
+        LOG_DATA.with(|log_data| log_data.borrow_mut().clear());
+        process_instruction(&Pubkey::default(), accounts, instruction_data)?;
+        Ok(RETURN_DATA.with(|return_data| return_data.borrow().clone()))
+    }
+
+    /// Encode an instruction in the calculator wire format
+    fn encode(operation: u8, left: i64, right: i64) -> Vec<u8> {
+        let mut data = vec![operation];
+        data.extend_from_slice(&left.to_le_bytes());
+        data.extend_from_slice(&right.to_le_bytes());
+        data
+    }
+
+    fn setup() -> (LiteSVM, Keypair, Pubkey) {
+        let mut svm = LiteSVM::new();
+        let payer = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+
+        let program_id = Keypair::new().pubkey();
+        svm.add_program_from_file(program_id, "target/deploy/byte_calculator.so")
+            .expect("Failed to load program");
+
+        (svm, payer, program_id)
+    }
+
+    /// Send a single instruction, expiring the blockhash first so repeated
+    /// identical instructions don't get rejected as duplicates
+    fn send(
+        svm: &mut LiteSVM,
+        instruction: Instruction,
+        payer: &Keypair,
+    ) -> Result<TransactionMetadata, TransactionError> {
+        svm.expire_blockhash();
+        let message = Message::new(&[instruction], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[payer], message, svm.latest_blockhash());
+        svm.send_transaction(transaction)
+            .map_err(|failed| failed.err)
+    }
+
+    /// Send one binary operation without a result account
+    fn send_operation(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        payer: &Keypair,
+        operation: u8,
+        left: i64,
+        right: i64,
+    ) -> Result<TransactionMetadata, TransactionError> {
+        let instruction =
+            Instruction::new_with_bytes(program_id, &encode(operation, left, right), vec![]);
+        send(svm, instruction, payer)
+    }
+
+    #[test]
+    fn test_wide_operands() {
+        let data = encode(2, 1_000_000, 1_000_000);
+        assert_eq!(data.len(), 17);
+        assert_eq!(read_operand(&data[1..9]), Ok(1_000_000));
+        assert_eq!(read_operand(&data[9..17]), Ok(1_000_000));
+
+        // 1_000_000 * 1_000_000 is far beyond what single-byte operands allowed
+        assert_eq!(run_i64(2, 1_000_000, 1_000_000), Ok(1_000_000_000_000));
+
+        // Negative operands survive the round trip as well
+        let data = encode(0, -5, i64::MIN);
+        assert_eq!(read_operand(&data[1..9]), Ok(-5));
+        assert_eq!(read_operand(&data[9..17]), Ok(i64::MIN));
+    }
+
+    #[test]
+    fn test_overflow_is_an_error() {
+        let overflowing = [
+            (0, i64::MAX, 1),
+            (1, i64::MIN, 1),
+            (2, i64::MAX, 2),
+            (3, i64::MIN, -1),
+            (4, i64::MIN, -1),
+            (5, 2, 63),
+            (5, 2, u32::MAX as i64 + 1),
+        ];
+
+        for (operation, left, right) in overflowing {
+            let data = encode(operation, left, right);
+            assert_eq!(
+                process_instruction(&Pubkey::default(), &[], &data),
+                Err(ProgramError::ArithmeticOverflow),
+                "opcode {} with {} and {} should overflow",
+                operation,
+                left,
+                right
+            );
+        }
+
+        // The largest in-range results still succeed
+        let data = encode(5, 2, 62);
+        assert!(process_instruction(&Pubkey::default(), &[], &data).is_ok());
+    }
+
+    /// Decode the i64 result an arithmetic or bitwise opcode returns
+    fn run_i64(operation: u8, left: i64, right: i64) -> Result<i64, ProgramError> {
+        let data = run(operation, left, right)?;
+        Ok(i64::from_le_bytes(data.try_into().expect("Result should be 8 bytes")))
+    }
+
+    #[test]
+    fn test_return_data() {
+        assert_eq!(run_i64(0, 1_000_000, -250), Ok(999_750));
+        assert_eq!(run_i64(3, 17, 5), Ok(3));
+        assert_eq!(run_i64(3, -17, 5), Ok(-3));
+
+        // Errors surface instead of a result
+        assert_eq!(run(3, 17, 0), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn test_bitwise_operations() {
+        assert_eq!(run_i64(6, 6, 3), Ok(2));
+        assert_eq!(run_i64(7, 6, 3), Ok(7));
+        assert_eq!(run_i64(8, 6, 3), Ok(5));
+        assert_eq!(run_i64(9, 1, 0), Ok(1));
+        assert_eq!(run_i64(9, 1, 63), Ok(i64::MIN));
+        assert_eq!(run_i64(10, -8, 1), Ok(-4));
+        assert_eq!(run_i64(10, i64::MIN, 63), Ok(-1));
+
+        // Shift amounts outside 0..64 are rejected for both directions
+        for (operation, right) in [(9, 64), (9, -1), (10, 64), (10, -1)] {
+            let data = encode(operation, 1, right);
+            assert_eq!(
+                process_instruction(&Pubkey::default(), &[], &data),
+                Err(ProgramError::InvalidInstructionData)
+            );
+        }
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let cases = [
+            (11, 5, 5, true),
+            (11, 5, -5, false),
+            (12, -1_000_000_000_000, 3, true),
+            (12, 3, 3, false),
+            (13, i64::MAX, i64::MIN, true),
+            (13, i64::MIN, i64::MAX, false),
+            (14, 1, 2, true),
+            (14, 2, 2, false),
+        ];
+
+        for (operation, left, right, expected) in cases {
+            assert_eq!(
+                run(operation, left, right),
+                Ok(vec![expected as u8]),
+                "opcode {} with {} and {}",
+                operation,
+                left,
+                right
+            );
+        }
+    }
+
+    #[test]
+    fn test_result_account() {
+        let (mut svm, payer, program_id) = setup();
+
+        // A zeroed, program-owned account to receive the result
+        let result_account = Pubkey::new_unique();
+        svm.set_account(
+            result_account,
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(CalcResult::LEN),
+                data: vec![0; CalcResult::LEN],
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to create result account");
+
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &encode(2, 6, -7),
+            vec![AccountMeta::new(result_account, false)],
+        );
+        let result = send(&mut svm, instruction, &payer);
+        assert!(result.is_ok(), "Multiply transaction should succeed");
+
+        let account = svm
+            .get_account(&result_account)
+            .expect("Failed to get result account");
+        let stored = CalcResult::try_from_slice(account.data())
+            .expect("Failed to deserialize result");
+        assert_eq!(
+            stored,
+            CalcResult {
+                last_op: 2,
+                operand_a: 6,
+                operand_b: -7,
+                result: -42,
+            }
+        );
+    }
+
+    /// Run raw instruction data natively behind a config account that enables
+    /// only the opcodes in `enabled_ops`
+    fn run_with_config(enabled_ops: u64, instruction_data: &[u8]) -> Result<Vec<u8>, ProgramError> {
+        let program_id = Pubkey::default();
+        let (address, bump) = find_config_address(&program_id);
+        let config = CalcConfig {
+            admin: Pubkey::new_unique(),
+            enabled_ops,
+            bump,
+        };
+        let mut lamports = 0;
+        let mut data = borsh::to_vec(&config).expect("Failed to serialize config");
+        let config_account = AccountInfo::new(
+            &address,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+        run_with_accounts(&[config_account], instruction_data)
+    }
+
+    #[test]
+    fn test_config_gates_operations() {
+        let add_and_subtract = 1 << 0 | 1 << 1;
+        let disabled = Err(ProgramError::Custom(CalcError::OperationDisabled as u32));
+
+        assert_eq!(
+            run_with_config(add_and_subtract, &encode(0, 2, 3)),
+            Ok(5i64.to_le_bytes().to_vec())
+        );
+        assert_eq!(
+            run_with_config(add_and_subtract, &encode(1, 2, 3)),
+            Ok((-1i64).to_le_bytes().to_vec())
+        );
+        let product = encode(2, 2, 3);
+        assert_eq!(run_with_config(add_and_subtract, &product), disabled);
+        let factorial = encode(FACTORIAL_OPCODE, 5, 0);
+        assert_eq!(run_with_config(add_and_subtract, &factorial), disabled);
+
+        // Expressions are checked opcode by opcode
+        let sum = rpn(&[push(2), push(3), vec![RPN_APPLY, 0]]);
+        assert_eq!(
+            run_with_config(add_and_subtract, &sum),
+            Ok(5i64.to_le_bytes().to_vec())
+        );
+        let rpn_product = rpn(&[push(2), push(3), vec![RPN_APPLY, 2]]);
+        assert_eq!(run_with_config(add_and_subtract, &rpn_product), disabled);
+
+        // Without a config account everything stays enabled
+        assert_eq!(run(2, 2, 3), Ok(6i64.to_le_bytes().to_vec()));
+    }
+
+    #[test]
+    fn test_set_config_on_chain() {
+        let (mut svm, payer, program_id) = setup();
+        let (config_address, _) = find_config_address(&program_id);
+        let unauthorized = Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CalcError::Unauthorized as u32),
+        ));
+
+        // The test loader keeps no program data, so stand one in with the
+        // payer as upgrade authority
+        let program_data_address = bpf_loader_upgradeable::get_program_data_address(&program_id);
+        let mut program_data = 3u32.to_le_bytes().to_vec();
+        program_data.extend_from_slice(&0u64.to_le_bytes());
+        program_data.push(1);
+        program_data.extend_from_slice(payer.pubkey().as_ref());
+        svm.set_account(
+            program_data_address,
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(program_data.len()),
+                data: program_data,
+                owner: bpf_loader_upgradeable::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to write program data account");
+
+        let set_config = |admin: &Keypair, enabled_ops: u64| {
+            let mut instruction_data = vec![CONFIG_MODE];
+            instruction_data.extend_from_slice(&enabled_ops.to_le_bytes());
+            Instruction::new_with_bytes(
+                program_id,
+                &instruction_data,
+                vec![
+                    AccountMeta::new(config_address, false),
+                    AccountMeta::new(admin.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(program_data_address, false),
+                ],
+            )
+        };
+        let operation = |operation: u8| {
+            Instruction::new_with_bytes(
+                program_id,
+                &encode(operation, 6, 7),
+                vec![AccountMeta::new_readonly(config_address, false)],
+            )
+        };
+
+        // Nobody but the upgrade authority can claim the config
+        let stranger = Keypair::new();
+        svm.airdrop(&stranger.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        assert_eq!(
+            send(&mut svm, set_config(&stranger, 0), &stranger).map(|_| ()),
+            unauthorized
+        );
+        assert!(svm.get_account(&config_address).is_none());
+
+        send(&mut svm, set_config(&payer, 1 << 0 | 1 << 1), &payer)
+            .expect("Creating the config should succeed");
+        let account = svm
+            .get_account(&config_address)
+            .expect("Failed to get config account");
+        let config =
+            CalcConfig::try_from_slice(account.data()).expect("Failed to deserialize config");
+        assert_eq!(config.admin, payer.pubkey());
+        assert_eq!(config.enabled_ops, 0b11);
+
+        assert!(send(&mut svm, operation(0), &payer).is_ok());
+        assert_eq!(
+            send(&mut svm, operation(2), &payer).map(|_| ()),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CalcError::OperationDisabled as u32)
+            ))
+        );
+
+        // Only the admin can change the mask
+        assert_eq!(
+            send(&mut svm, set_config(&stranger, ALL_OPS), &stranger).map(|_| ()),
+            unauthorized
+        );
+
+        send(&mut svm, set_config(&payer, ALL_OPS), &payer)
+            .expect("Updating the config should succeed");
+        assert!(send(&mut svm, operation(2), &payer).is_ok());
+
+        // Passing the config again as the result account must not overwrite it
+        let clobber = Instruction::new_with_bytes(
+            program_id,
+            &encode(0, 6, 7),
+            vec![
+                AccountMeta::new_readonly(config_address, false),
+                AccountMeta::new(config_address, false),
+            ],
+        );
+        assert_eq!(
+            send(&mut svm, clobber, &payer).map(|_| ()),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::InvalidArgument
+            ))
+        );
+        let account = svm
+            .get_account(&config_address)
+            .expect("Failed to get config account");
+        let config =
+            CalcConfig::try_from_slice(account.data()).expect("Failed to deserialize config");
+        assert_eq!(config.admin, payer.pubkey());
+        assert_eq!(config.enabled_ops, ALL_OPS);
+    }
+
+    /// Run a unary opcode and decode its i64 result
+    fn run_unary(operation: u8, operand: i64) -> Result<i64, ProgramError> {
+        let mut instruction_data = vec![operation];
+        instruction_data.extend_from_slice(&operand.to_le_bytes());
+        let data = run_bytes(&instruction_data)?;
+        Ok(i64::from_le_bytes(data.try_into().expect("Result should be 8 bytes")))
+    }
+
+    #[test]
+    fn test_unary_operations() {
+        assert_eq!(run_unary(15, 144), Ok(12));
+        assert_eq!(run_unary(15, 0), Ok(0));
+        assert_eq!(run_unary(15, 1), Ok(1));
+        assert_eq!(run_unary(15, 143), Ok(11));
+        assert_eq!(run_unary(15, i64::MAX), Ok(3_037_000_499));
+        assert_eq!(run_unary(16, -5), Ok(5));
+        assert_eq!(run_unary(17, 7), Ok(-7));
+
+        assert_eq!(run_unary(FACTORIAL_OPCODE, 5), Ok(120));
+        assert_eq!(run_unary(FACTORIAL_OPCODE, 0), Ok(1));
+        assert_eq!(run_unary(FIBONACCI_OPCODE, 10), Ok(55));
+        assert_eq!(run_unary(FIBONACCI_OPCODE, 0), Ok(0));
+        assert_eq!(
+            run_unary(FIBONACCI_OPCODE, 92),
+            Ok(7_540_113_804_746_346_429)
+        );
+
+        // Negative square roots or factorials and unrepresentable results are
+        // errors
+        assert_eq!(run_unary(15, -1), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(run_unary(16, i64::MIN), Err(ProgramError::ArithmeticOverflow));
+        assert_eq!(run_unary(17, i64::MIN), Err(ProgramError::ArithmeticOverflow));
+        assert_eq!(
+            run_unary(FACTORIAL_OPCODE, -1),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            run_unary(FACTORIAL_OPCODE, 21),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+        assert_eq!(
+            run_unary(FIBONACCI_OPCODE, -1),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        // fib(93) would fit a u64 but not an i64
+        for n in [93, 94] {
+            assert_eq!(
+                run_unary(FIBONACCI_OPCODE, n),
+                Err(ProgramError::ArithmeticOverflow)
+            );
+        }
+    }
+
+    #[test]
+    fn test_basis_points() {
+        // 2.5% of 10000
+        assert_eq!(run_i64(BPS_OPCODE, 10_000, 250), Ok(250));
+        assert_eq!(run_i64(BPS_OPCODE, 10_000, 10_000), Ok(10_000));
+        assert_eq!(run_i64(BPS_OPCODE, -8_000, 125), Ok(-100));
+
+        // The intermediate product overflows an i64 but the result fits
+        assert_eq!(run_i64(BPS_OPCODE, i64::MAX, 5_000), Ok(i64::MAX / 2));
+
+        assert_eq!(
+            run_i64(BPS_OPCODE, i64::MAX, 10_001),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+        assert_eq!(
+            run_i64(BPS_OPCODE, 10_000, -1),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    /// Run one binary opcode in unsigned mode and decode the u64 result
+    fn run_u64(operation: u8, left: u64, right: u64) -> Result<u64, ProgramError> {
+        let mut instruction_data = vec![UNSIGNED_MODE, operation];
+        instruction_data.extend_from_slice(&left.to_le_bytes());
+        instruction_data.extend_from_slice(&right.to_le_bytes());
+        let return_data = run_bytes(&instruction_data)?;
+        Ok(u64::from_le_bytes(return_data.try_into().unwrap()))
+    }
+
+    #[test]
+    fn test_unsigned_mode() {
+        // Signed mode goes negative, unsigned mode refuses to
+        assert_eq!(run_i64(1, 5, 8), Ok(-3));
+        assert_eq!(run_u64(1, 5, 8), Err(ProgramError::ArithmeticOverflow));
+        assert_eq!(run_u64(1, 8, 5), Ok(3));
+
+        // Values past i64::MAX are ordinary operands in unsigned mode
+        assert_eq!(run_u64(0, u64::MAX - 1, 1), Ok(u64::MAX));
+        assert_eq!(run_u64(13, u64::MAX, 1), Ok(1));
+        assert_eq!(
+            run_u64(0, u64::MAX, 1),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+
+        // The exponent ceiling matches signed mode
+        assert_eq!(run_u64(5, 2, 63), Ok(1 << 63));
+        assert_eq!(run_u64(5, 2, 64), Err(ProgramError::ArithmeticOverflow));
+        assert_eq!(run_u64(5, 1, 64), Ok(1));
+
+        assert_eq!(run_u64(3, 1, 0), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(run_u64(17, 1, 0), Err(CalcError::UnknownOpcode.into()));
+        assert_eq!(
+            run_bytes(&[UNSIGNED_MODE, 1]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_gcd_and_lcm() {
+        assert_eq!(run_i64(18, 48, 36), Ok(12));
+        assert_eq!(run_i64(18, -48, 36), Ok(12));
+        assert_eq!(run_i64(18, 0, 7), Ok(7));
+        assert_eq!(run_i64(19, 4, 6), Ok(12));
+        assert_eq!(run_i64(19, -4, 6), Ok(12));
+        assert_eq!(run_i64(19, 0, 5), Ok(0));
+
+        // Results beyond i64 report overflow
+        assert_eq!(run(18, i64::MIN, 0), Err(ProgramError::ArithmeticOverflow));
+        assert_eq!(run(19, i64::MAX, i64::MAX - 1), Err(ProgramError::ArithmeticOverflow));
+    }
+
+    /// RPN token pushing `value`
+    fn push(value: i64) -> Vec<u8> {
+        [&[RPN_PUSH][..], &value.to_le_bytes()].concat()
+    }
+
+    /// Instruction data for an RPN expression made of `tokens`
+    fn rpn(tokens: &[Vec<u8>]) -> Vec<u8> {
+        [vec![RPN_MODE], tokens.concat()].concat()
+    }
+
+    #[test]
+    fn test_rpn_expression() {
+        // 3 4 + 5 *
+        let expression = rpn(&[push(3), push(4), vec![RPN_APPLY, 0], push(5), vec![RPN_APPLY, 2]]);
+        assert_eq!(run_bytes(&expression), Ok(35i64.to_le_bytes().to_vec()));
+
+        // An operator with fewer than two operands underflows
+        let underflow = rpn(&[push(3), vec![RPN_APPLY, 0]]);
+        assert_eq!(run_bytes(&underflow), Err(ProgramError::InvalidInstructionData));
+
+        // Leftover operands are rejected, as is an empty expression
+        let leftover = rpn(&[push(3), push(4)]);
+        assert_eq!(run_bytes(&leftover), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(run_bytes(&rpn(&[])), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn test_divmod() {
+        let data = run(20, 17, 5).expect("Divmod should succeed");
+        assert_eq!(data.len(), 16);
+        assert_eq!(i64::from_le_bytes(data[..8].try_into().unwrap()), 3);
+        assert_eq!(i64::from_le_bytes(data[8..].try_into().unwrap()), 2);
+
+        assert_eq!(run(20, 17, 0), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(run(20, i64::MIN, -1), Err(ProgramError::ArithmeticOverflow));
+    }
+
+    /// Run a modpow instruction natively and decode its i64 result
+    fn run_modpow(base: i64, exponent: i64, modulus: i64) -> Result<i64, ProgramError> {
+        let mut data = encode(MODPOW_OPCODE, base, exponent);
+        data.extend_from_slice(&modulus.to_le_bytes());
+        let bytes = run_bytes(&data)?;
+        Ok(i64::from_le_bytes(
+            bytes.try_into().expect("Result should be 8 bytes"),
+        ))
+    }
+
+    #[test]
+    fn test_modpow() {
+        assert_eq!(run_modpow(2, 10, 1000), Ok(24));
+        assert_eq!(run_modpow(3, 0, 7), Ok(1));
+        assert_eq!(run_modpow(5, 3, 1), Ok(0));
+        assert_eq!(run_modpow(-2, 3, 5), Ok(2));
+        // 561 is a Carmichael number, so any coprime base to the 560th is 1
+        assert_eq!(run_modpow(7, 560, 561), Ok(1));
+
+        assert_eq!(
+            run_modpow(2, 10, 0),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            run_modpow(2, -1, 7),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            run_modpow(i64::MAX - 1, 2, i64::MAX),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+
+        // Two operands are not enough
+        assert_eq!(
+            run_bytes(&encode(MODPOW_OPCODE, 2, 10)),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    /// Encode a list opcode followed by its operand count and operands
+    fn encode_list(operation: u8, values: &[i64]) -> Vec<u8> {
+        let mut data = vec![operation];
+        data.extend_from_slice(&(values.len() as u32).to_le_bytes());
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    /// Run a list instruction natively and decode its i64 result
+    fn run_list(operation: u8, values: &[i64]) -> Result<i64, ProgramError> {
+        let bytes = run_bytes(&encode_list(operation, values))?;
+        Ok(i64::from_le_bytes(
+            bytes.try_into().expect("Result should be 8 bytes"),
+        ))
+    }
+
+    #[test]
+    fn test_sum_and_product_all() {
+        assert_eq!(run_list(SUM_ALL_OPCODE, &[1, 2, 3, 4]), Ok(10));
+        assert_eq!(run_list(PRODUCT_ALL_OPCODE, &[2, 3, 4]), Ok(24));
+        assert_eq!(run_list(SUM_ALL_OPCODE, &[]), Ok(0));
+        assert_eq!(run_list(PRODUCT_ALL_OPCODE, &[]), Ok(1));
+        assert_eq!(run_list(SUM_ALL_OPCODE, &[-5, 5, i64::MIN]), Ok(i64::MIN));
+
+        assert_eq!(
+            run_list(SUM_ALL_OPCODE, &[i64::MAX, 1]),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+        assert_eq!(
+            run_list(PRODUCT_ALL_OPCODE, &[i64::MAX, 2, 0]),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+
+        // The count must match the operands that follow it exactly
+        let mut data = encode_list(SUM_ALL_OPCODE, &[1, 2, 3]);
+        data[1] = 4;
+        assert_eq!(run_bytes(&data), Err(ProgramError::InvalidInstructionData));
+        data[1] = 2;
+        assert_eq!(run_bytes(&data), Err(ProgramError::InvalidInstructionData));
+        let mut data = encode_list(PRODUCT_ALL_OPCODE, &[2, 3]);
+        data.pop();
+        assert_eq!(run_bytes(&data), Err(ProgramError::InvalidInstructionData));
+
+        // A count that would not fit the instruction is rejected, not trusted
+        let mut data = encode_list(SUM_ALL_OPCODE, &[]);
+        data[1..5].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(run_bytes(&data), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(
+            run_bytes(&[SUM_ALL_OPCODE, 0, 0]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    /// Decode the events logged by the last native run on this thread
+    fn logged_events() -> Vec<CalcEvent> {
+        LOG_DATA.with(|log_data| {
+            log_data
+                .borrow()
+                .iter()
+                .map(|field| {
+                    CalcEvent::try_from_slice(field).expect("Log data should be a CalcEvent")
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_calc_event_emitted() {
+        run(0, 15, 7).expect("Add should succeed");
+        assert_eq!(
+            logged_events(),
+            vec![CalcEvent {
+                op: 0,
+                a: 15,
+                b: 7,
+                result: 22,
+            }]
+        );
+
+        run(20, 17, 5).expect("Divmod should succeed");
+        assert_eq!(
+            logged_events(),
+            vec![CalcEvent {
+                op: DIVMOD_OPCODE,
+                a: 17,
+                b: 5,
+                result: 3,
+            }]
+        );
+
+        run_list(SUM_ALL_OPCODE, &[1, 2, 3]).expect("SumAll should succeed");
+        assert_eq!(
+            logged_events(),
+            vec![CalcEvent {
+                op: SUM_ALL_OPCODE,
+                a: 0,
+                b: 0,
+                result: 6,
+            }]
+        );
+
+        // A failed computation logs no event
+        assert!(run(3, 1, 0).is_err());
+        assert!(logged_events().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_instructions() {
+        assert_eq!(
+            run(BPS_OPCODE + 1, 1, 2),
+            Err(ProgramError::Custom(CalcError::UnknownOpcode as u32))
+        );
+
+        // Anything shorter than a full opcode + operands is an error
+        assert_eq!(run_bytes(&[]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(
+            run_bytes(&encode(0, 1, 2)[..16]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_short_data_fails() {
+        // A lone opcode used to "succeed" without doing anything
+        assert_eq!(run_bytes(&[2]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(run_bytes(&[15]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(
+            run_bytes(&[15, 0, 0, 0]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_every_opcode_on_chain() {
+        let (mut svm, payer, program_id) = setup();
+
+        let cases: [(u8, i64, i64, &[u8]); 20] = [
+            (0, 15, 7, &22i64.to_le_bytes()),
+            (1, 20, 8, &12i64.to_le_bytes()),
+            (2, 6, 4, &24i64.to_le_bytes()),
+            (3, 24, 6, &4i64.to_le_bytes()),
+            (4, 17, 5, &2i64.to_le_bytes()),
+            (5, 3, 4, &81i64.to_le_bytes()),
+            (6, 6, 3, &2i64.to_le_bytes()),
+            (7, 6, 3, &7i64.to_le_bytes()),
+            (8, 6, 3, &5i64.to_le_bytes()),
+            (9, 1, 10, &1024i64.to_le_bytes()),
+            (10, 1024, 3, &128i64.to_le_bytes()),
+            (11, 7, 7, &[1]),
+            (12, 3, 9, &[1]),
+            (13, 3, 9, &[0]),
+            (14, 3, 9, &[1]),
+            (15, 144, 0, &12i64.to_le_bytes()),
+            (16, -5, 0, &5i64.to_le_bytes()),
+            (17, 7, 0, &(-7i64).to_le_bytes()),
+            (18, 48, 36, &12i64.to_le_bytes()),
+            (19, 4, 6, &12i64.to_le_bytes()),
+        ];
+
+        for (operation, left, right, expected) in cases {
+            let meta = send_operation(&mut svm, program_id, &payer, operation, left, right)
+                .unwrap_or_else(|err| panic!("opcode {} failed: {:?}", operation, err));
+            assert_eq!(meta.return_data.program_id, program_id);
+            assert_eq!(meta.return_data.data, expected, "opcode {}", operation);
+        }
+
+        let meta = send_operation(&mut svm, program_id, &payer, 20, 17, 5)
+            .expect("Divmod transaction should succeed");
+        assert_eq!(
+            meta.return_data.data,
+            [3i64.to_le_bytes(), 2i64.to_le_bytes()].concat()
+        );
+    }
+
+    #[test]
+    fn test_invalid_operations_on_chain() {
+        let (mut svm, payer, program_id) = setup();
+
+        let invalid = [
+            (3, 24, 0, "divide by zero"),
+            (4, 24, 0, "modulus by zero"),
+            (5, 3, -1, "negative exponent"),
+        ];
+        for (operation, left, right, description) in invalid {
+            let result = send_operation(&mut svm, program_id, &payer, operation, left, right);
+            assert_eq!(
+                result.unwrap_err(),
+                TransactionError::InstructionError(0, InstructionError::InvalidInstructionData),
+                "{} should fail",
+                description
+            );
+        }
+    }
+
+    /// Ceiling on the compute units any single opcode may use. Every operation
+    /// sits far below it today, so crossing it flags a costly change.
+    const MAX_COMPUTE_UNITS: u64 = 20_000;
+
+    #[test]
+    fn test_compute_units_per_opcode() {
+        let (mut svm, payer, program_id) = setup();
+
+        let cases = [
+            (0, 15, 7, "Add"),
+            (1, 20, 8, "Subtract"),
+            (2, 6, 4, "Multiply"),
+            (3, 24, 6, "Divide"),
+            (4, 17, 5, "Modulus"),
+            (5, 3, 39, "Power"),
+            (6, 6, 3, "AND"),
+            (7, 6, 3, "OR"),
+            (8, 6, 3, "XOR"),
+            (9, 1, 10, "Shift left"),
+            (10, 1024, 3, "Shift right"),
+            (11, 7, 7, "Equal"),
+            (12, 3, 9, "Less than"),
+            (13, 3, 9, "Greater than"),
+            (14, 3, 9, "Not equal"),
+            (15, i64::MAX, 0, "Square root"),
+            (16, -5, 0, "Absolute value"),
+            (17, 7, 0, "Negate"),
+            (18, 1_836_311_903, 1_134_903_170, "GCD"),
+            (19, 4, 6, "LCM"),
+            (20, 17, 5, "Divmod"),
+            // The longest loop Fibonacci will run
+            (FIBONACCI_OPCODE, 92, 0, "Fibonacci"),
+        ];
+
+        println!("{:<16} {:>14}", "Operation", "Compute units");
+        for (operation, left, right, name) in cases {
+            let meta = send_operation(&mut svm, program_id, &payer, operation, left, right)
+                .unwrap_or_else(|err| panic!("{} failed: {:?}", name, err));
+            println!("{:<16} {:>14}", name, meta.compute_units_consumed);
+            assert!(
+                meta.compute_units_consumed <= MAX_COMPUTE_UNITS,
+                "{} used {} compute units",
+                name,
+                meta.compute_units_consumed
+            );
+        }
+    }
+
+    #[test]
+    fn test_huge_exponent_rejected_early() {
+        let overflow = Err(ProgramError::ArithmeticOverflow);
+        assert_eq!(compute(5, 2, MAX_EXPONENT + 1), overflow);
+        assert_eq!(compute(5, -3, i64::MAX), overflow);
+        assert_eq!(compute(5, -2, MAX_EXPONENT), Ok(i64::MIN));
+
+        // Bases 0 and ±1 never overflow, so the ceiling does not apply
+        assert_eq!(compute(5, 1, 1_000), Ok(1));
+        assert_eq!(compute(5, -1, 1_001), Ok(-1));
+        assert_eq!(compute(5, 0, 1_000), Ok(0));
+
+        let (mut svm, payer, program_id) = setup();
+        let instruction = Instruction::new_with_bytes(program_id, &encode(5, 3, i64::MAX), vec![]);
+        let message = Message::new(&[instruction], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, svm.latest_blockhash());
+        let failed = svm
+            .send_transaction(transaction)
+            .expect_err("A huge exponent should be rejected");
+        assert!(failed
+            .meta
+            .logs
+            .iter()
+            .any(|log| log.contains(&format!("Exponent {} is above the ceiling", i64::MAX))));
+
+        // Rejecting costs less than the largest power that succeeds
+        let largest =
+            send_operation(&mut svm, program_id, &payer, 5, 3, 39).expect("3 ^ 39 should succeed");
+        assert!(
+            failed.meta.compute_units_consumed <= largest.compute_units_consumed,
+            "Rejecting used {} compute units, 3 ^ 39 used {}",
+            failed.meta.compute_units_consumed,
+            largest.compute_units_consumed
+        );
+    }
+
+    #[test]
+    fn test_compute_every_opcode() {
+        let invalid = Err(ProgramError::InvalidInstructionData);
+        let overflow = Err(ProgramError::ArithmeticOverflow);
+        let cases = [
+            (0, 15, 7, Ok(22)),
+            (0, i64::MAX, 1, overflow.clone()),
+            (1, 20, 8, Ok(12)),
+            (1, i64::MIN, 1, overflow.clone()),
+            (2, 6, 4, Ok(24)),
+            (2, i64::MAX, 2, overflow.clone()),
+            (3, 24, 6, Ok(4)),
+            (3, 24, 0, invalid.clone()),
+            (3, i64::MIN, -1, overflow.clone()),
+            (4, 17, 5, Ok(2)),
+            (4, 17, 0, invalid.clone()),
+            (5, 3, 4, Ok(81)),
+            (5, 3, -1, invalid.clone()),
+            (5, 2, 64, overflow.clone()),
+            (6, 6, 3, Ok(2)),
+            (7, 6, 3, Ok(7)),
+            (8, 6, 3, Ok(5)),
+            (9, 1, 10, Ok(1024)),
+            (9, 1, 64, invalid.clone()),
+            (10, 1024, 3, Ok(128)),
+            (10, -8, 1, Ok(-4)),
+            (10, 1, -1, invalid.clone()),
+            (11, 7, 7, Ok(1)),
+            (12, 3, 9, Ok(1)),
+            (13, 3, 9, Ok(0)),
+            (14, 3, 9, Ok(1)),
+            (15, 144, 0, Ok(12)),
+            (15, -1, 0, invalid.clone()),
+            (16, -5, 0, Ok(5)),
+            (16, i64::MIN, 0, overflow.clone()),
+            (17, 7, 0, Ok(-7)),
+            (17, i64::MIN, 0, overflow.clone()),
+            (18, 48, 36, Ok(12)),
+            (19, 4, 6, Ok(12)),
+            (19, i64::MAX, i64::MAX - 1, overflow.clone()),
+        ];
+        for (operation, left, right, expected) in cases {
+            assert_eq!(
+                compute(operation, left, right),
+                expected,
+                "opcode {} with {} and {}",
+                operation,
+                left,
+                right
+            );
+        }
+
+        // Divmod, modpow, list and unassigned opcodes are not single-result
+        // operations
+        for operation in [
+            DIVMOD_OPCODE,
+            MODPOW_OPCODE,
+            SUM_ALL_OPCODE,
+            PRODUCT_ALL_OPCODE,
+            BPS_OPCODE + 1,
+            RPN_MODE,
+        ] {
+            assert_eq!(
+                compute(operation, 1, 1),
+                Err(CalcError::UnknownOpcode.into())
+            );
+        }
+    }
+
+    /// Reference semantics for the binary opcodes that map directly onto
+    /// `checked_*` and the standard operators
+    fn reference(operation: u8, left: i64, right: i64) -> Result<i64, ProgramError> {
+        let result = match operation {
+            0 => left.checked_add(right),
+            1 => left.checked_sub(right),
+            2 => left.checked_mul(right),
+            3 | 4 if right == 0 => return Err(ProgramError::InvalidInstructionData),
+            3 => left.checked_div(right),
+            4 => left.checked_rem(right),
+            6 => Some(left & right),
+            7 => Some(left | right),
+            8 => Some(left ^ right),
+            11 => Some((left == right) as i64),
+            12 => Some((left < right) as i64),
+            13 => Some((left > right) as i64),
+            14 => Some((left != right) as i64),
+            _ => unreachable!("no reference for opcode {}", operation),
+        };
+        result.ok_or(ProgramError::ArithmeticOverflow)
+    }
+
+    /// Operands that mix uniformly random values with the edge cases random
+    /// sampling rarely hits
+    fn operand() -> impl Strategy<Value = i64> {
+        prop_oneof![
+            any::<i64>(),
+            Just(i64::MIN),
+            Just(i64::MAX),
+            Just(-1),
+            Just(0),
+            Just(1),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_compute_matches_reference(
+            operation in prop::sample::select(vec![0u8, 1, 2, 3, 4, 6, 7, 8, 11, 12, 13, 14]),
+            left in operand(),
+            right in operand(),
+        ) {
+            prop_assert_eq!(compute(operation, left, right), reference(operation, left, right));
+        }
+
+        #[test]
+        fn prop_power_matches_checked_pow(base in -100i64..100, exponent in -4i64..70) {
+            let expected = match u32::try_from(exponent) {
+                Ok(exponent) => base
+                    .checked_pow(exponent)
+                    .ok_or(ProgramError::ArithmeticOverflow),
+                Err(_) => Err(ProgramError::InvalidInstructionData),
+            };
+            prop_assert_eq!(compute(5, base, exponent), expected);
+        }
+
+        #[test]
+        fn prop_shifts_reject_out_of_range(value in operand(), shift in -70i64..70) {
+            let (left, right) = if (0..64).contains(&shift) {
+                (Ok(value << shift), Ok(value >> shift))
+            } else {
+                let error = Err(ProgramError::InvalidInstructionData);
+                (error.clone(), error)
+            };
+            prop_assert_eq!(compute(9, value, shift), left);
+            prop_assert_eq!(compute(10, value, shift), right);
+        }
+    }
+}
@@ -0,0 +1,483 @@
+//! Checked integer arithmetic shared by the calculator and counter programs
+//!
+//! Nothing here depends on `solana_program` or `std`, so the same code runs
+//! on-chain and in plain unit tests. Every operation reports overflow as an
+//! error instead of wrapping or panicking.
+#![cfg_attr(not(test), no_std)]
+
+/// Why an operation produced no result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathError {
+    /// The result does not fit in the operand type
+    Overflow,
+
+    /// An operand is outside the operation's domain, e.g. division by zero
+    InvalidOperand,
+
+    /// The opcode does not name any operation
+    UnknownOpcode,
+}
+
+/// Largest exponent the power opcode attempts for bases other than 0 and ±1;
+/// `(-2) ^ 63` is the last power of such a base that fits in an i64
+pub const MAX_EXPONENT: i64 = 63;
+
+/// Largest `n` whose factorial fits in an i64
+pub const MAX_FACTORIAL: i64 = 20;
+
+/// Largest `n` whose Fibonacci number fits in an i64; `fib(93)` would need a
+/// u64
+pub const MAX_FIBONACCI: i64 = 92;
+
+/// Apply one calculator opcode to two operands
+///
+/// Opcodes 0 to 19 are add, subtract, multiply, divide, modulus, power, AND,
+/// OR, XOR, left shift, right shift, `==`, `<`, `>`, `!=`, square root,
+/// absolute value, negation, GCD and LCM, and opcodes 24 to 26 are factorial,
+/// Fibonacci and basis points. Unary opcodes only read `a`, and comparisons
+/// yield 1 for true and 0 for false.
+pub fn checked_op(op: u8, a: i64, b: i64) -> Result<i64, MathError> {
+    let result = match op {
+        0 => a.checked_add(b),
+        1 => a.checked_sub(b),
+        2 => a.checked_mul(b),
+        3 | 4 if b == 0 => return Err(MathError::InvalidOperand),
+        3 => a.checked_div(b),
+        4 => a.checked_rem(b),
+        5 => return checked_pow(a, b),
+        6 => Some(a & b),
+        7 => Some(a | b),
+        8 => Some(a ^ b),
+        9 | 10 if !(0..64).contains(&b) => return Err(MathError::InvalidOperand),
+        9 => Some(a << b),
+        10 => Some(a >> b),
+        11 => Some((a == b) as i64),
+        12 => Some((a < b) as i64),
+        13 => Some((a > b) as i64),
+        14 => Some((a != b) as i64),
+        15 if a < 0 => return Err(MathError::InvalidOperand),
+        15 => Some(isqrt(a)),
+        16 => a.checked_abs(),
+        17 => a.checked_neg(),
+        18 => i64::try_from(gcd(a.unsigned_abs(), b.unsigned_abs())).ok(),
+        19 => lcm(a, b),
+        24 => return factorial(a),
+        25 => return fibonacci(a),
+        26 => return basis_points(a, b),
+        _ => return Err(MathError::UnknownOpcode),
+    };
+
+    result.ok_or(MathError::Overflow)
+}
+
+/// Apply one binary calculator opcode to two unsigned operands
+///
+/// Covers opcodes 0 to 14, 18 and 19 with the same meaning as `checked_op`,
+/// except that a result below zero is an overflow: `5 - 8` fails here where
+/// `checked_op` yields -3. Exponents above `MAX_EXPONENT` are refused the same
+/// way `checked_pow` refuses them.
+pub fn checked_op_unsigned(op: u8, a: u64, b: u64) -> Result<u64, MathError> {
+    let result = match op {
+        0 => a.checked_add(b),
+        1 => a.checked_sub(b),
+        2 => a.checked_mul(b),
+        3 | 4 if b == 0 => return Err(MathError::InvalidOperand),
+        3 => a.checked_div(b),
+        4 => a.checked_rem(b),
+        5 if b > MAX_EXPONENT as u64 && a > 1 => return Err(MathError::Overflow),
+        5 => u32::try_from(b)
+            .ok()
+            .and_then(|exponent| a.checked_pow(exponent)),
+        6 => Some(a & b),
+        7 => Some(a | b),
+        8 => Some(a ^ b),
+        9 | 10 if b >= 64 => return Err(MathError::InvalidOperand),
+        9 => Some(a << b),
+        10 => Some(a >> b),
+        11 => Some((a == b) as u64),
+        12 => Some((a < b) as u64),
+        13 => Some((a > b) as u64),
+        14 => Some((a != b) as u64),
+        18 => Some(gcd(a, b)),
+        19 if a == 0 || b == 0 => Some(0),
+        19 => (a / gcd(a, b)).checked_mul(b),
+        _ => return Err(MathError::UnknownOpcode),
+    };
+
+    result.ok_or(MathError::Overflow)
+}
+
+/// Raise `base` to a non-negative `exponent`
+///
+/// Exponents above `MAX_EXPONENT` are refused as overflow without any
+/// multiplying unless the base is 0 or ±1, whose powers always fit.
+pub fn checked_pow(base: i64, exponent: i64) -> Result<i64, MathError> {
+    if exponent < 0 {
+        return Err(MathError::InvalidOperand);
+    }
+    if exponent > MAX_EXPONENT && base.unsigned_abs() > 1 {
+        return Err(MathError::Overflow);
+    }
+
+    // Exponents that do not fit in u32 are treated as overflow
+    u32::try_from(exponent)
+        .ok()
+        .and_then(|exponent| base.checked_pow(exponent))
+        .ok_or(MathError::Overflow)
+}
+
+/// `n!` for a non-negative `n`, refusing `n` above `MAX_FACTORIAL` as
+/// overflow before multiplying
+pub fn factorial(n: i64) -> Result<i64, MathError> {
+    if n < 0 {
+        return Err(MathError::InvalidOperand);
+    }
+    if n > MAX_FACTORIAL {
+        return Err(MathError::Overflow);
+    }
+
+    (2..=n).try_fold(1i64, |product, factor| {
+        product.checked_mul(factor).ok_or(MathError::Overflow)
+    })
+}
+
+/// The `n`th Fibonacci number for a non-negative `n`, with `fib(0) == 0`,
+/// computed iteratively. `n` above `MAX_FIBONACCI` is refused as overflow
+/// before iterating, which bounds the loop at 92 steps.
+pub fn fibonacci(n: i64) -> Result<i64, MathError> {
+    if n < 0 {
+        return Err(MathError::InvalidOperand);
+    }
+    if n > MAX_FIBONACCI {
+        return Err(MathError::Overflow);
+    }
+
+    // Starting from fib(-1) = 1 means the loop never computes past fib(n)
+    let (mut previous, mut current) = (1i64, 0i64);
+    for _ in 0..n {
+        let next = previous.checked_add(current).ok_or(MathError::Overflow)?;
+        (previous, current) = (current, next);
+    }
+    Ok(current)
+}
+
+/// Basis points in a whole: 10000 bps is 100%
+pub const BPS_DENOMINATOR: i64 = 10_000;
+
+/// `amount * bps / 10000` for a non-negative `bps`, rounded towards zero.
+/// The product is taken in i128, so only a final result outside i64
+/// overflows, e.g. more than 100% of an amount near `i64::MAX`.
+pub fn basis_points(amount: i64, bps: i64) -> Result<i64, MathError> {
+    if bps < 0 {
+        return Err(MathError::InvalidOperand);
+    }
+
+    let scaled = i128::from(amount)
+        .checked_mul(i128::from(bps))
+        .ok_or(MathError::Overflow)?
+        / i128::from(BPS_DENOMINATOR);
+    i64::try_from(scaled).map_err(|_| MathError::Overflow)
+}
+
+/// Integer square root of a non-negative value, rounded down, using Newton's
+/// method since floats are not available on-chain
+pub fn isqrt(value: i64) -> i64 {
+    if value < 2 {
+        return value;
+    }
+
+    // Start above the root and step down until the estimate stops shrinking
+    let mut estimate = value / 2 + 1;
+    let mut next = (estimate + value / estimate) / 2;
+    while next < estimate {
+        estimate = next;
+        next = (estimate + value / estimate) / 2;
+    }
+    estimate
+}
+
+/// Modular exponentiation by square-and-multiply. Reducing after every step
+/// keeps intermediate values below `modulus` squared, so only moduli past
+/// `i64::MAX.isqrt()` can overflow. The result is in `0..modulus.abs()`.
+pub fn modpow(base: i64, exponent: i64, modulus: i64) -> Result<i64, MathError> {
+    if modulus == 0 || exponent < 0 {
+        return Err(MathError::InvalidOperand);
+    }
+
+    let modulus = modulus.checked_abs().ok_or(MathError::Overflow)?;
+    let mul_mod = |a: i64, b: i64| {
+        a.checked_mul(b)
+            .map(|product| product % modulus)
+            .ok_or(MathError::Overflow)
+    };
+
+    let mut base = base.rem_euclid(modulus);
+    let mut exponent = exponent;
+    let mut result = 1 % modulus;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = mul_mod(base, base)?;
+        }
+    }
+    Ok(result)
+}
+
+/// Greatest common divisor using the Euclidean algorithm
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Least common multiple of the absolute values, or 0 if either operand is 0.
+/// Dividing by the GCD before multiplying keeps intermediate values small.
+pub fn lcm(a: i64, b: i64) -> Option<i64> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+
+    let (a, b) = (a.unsigned_abs(), b.unsigned_abs());
+    let multiple = (a / gcd(a, b)).checked_mul(b)?;
+    i64::try_from(multiple).ok()
+}
+
+/// Add `amount` to `value` without passing `max`, returning the new value
+/// and the part of `amount` that did not fit
+pub fn clamped_add(value: u64, amount: u64, max: u64) -> (u64, u64) {
+    let added = amount.min(max.saturating_sub(value));
+    (value + added, amount - added)
+}
+
+/// Subtract `amount` from `value` without passing `min`, returning the new
+/// value and the part of `amount` that could not be taken
+pub fn clamped_sub(value: u64, amount: u64, min: u64) -> (u64, u64) {
+    let subtracted = amount.min(value.saturating_sub(min));
+    (value - subtracted, amount - subtracted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_every_op() {
+        let cases = [
+            (0, 15, 7, 22),
+            (1, 20, 8, 12),
+            (2, 6, 4, 24),
+            (3, -24, 6, -4),
+            (4, -17, 5, -2),
+            (5, 3, 4, 81),
+            (6, 6, 3, 2),
+            (7, 6, 3, 7),
+            (8, 6, 3, 5),
+            (9, 1, 10, 1024),
+            (10, -1024, 3, -128),
+            (11, 7, 7, 1),
+            (12, 3, 9, 1),
+            (13, 3, 9, 0),
+            (14, 3, 9, 1),
+            (15, 99, 0, 9),
+            (16, -5, 0, 5),
+            (17, 7, 0, -7),
+            (18, -48, 36, 12),
+            (19, 4, -6, 12),
+            (24, 5, 0, 120),
+            (25, 10, 0, 55),
+            (26, 10_000, 250, 250),
+        ];
+        for (op, a, b, expected) in cases {
+            assert_eq!(checked_op(op, a, b), Ok(expected), "op {}", op);
+        }
+        assert_eq!(checked_op(20, 1, 1), Err(MathError::UnknownOpcode));
+    }
+
+    #[test]
+    fn test_overflow_boundaries() {
+        let overflowing = [
+            (0, i64::MAX, 1),
+            (1, i64::MIN, 1),
+            (2, i64::MAX, 2),
+            (3, i64::MIN, -1),
+            (4, i64::MIN, -1),
+            (5, 2, 63),
+            (16, i64::MIN, 0),
+            (17, i64::MIN, 0),
+            (18, i64::MIN, 0),
+            (19, i64::MAX, i64::MAX - 1),
+        ];
+        for (op, a, b) in overflowing {
+            assert_eq!(checked_op(op, a, b), Err(MathError::Overflow), "op {}", op);
+        }
+
+        // The last values on the right side of each boundary still succeed
+        let in_range = [
+            (0, i64::MAX - 1, 1, i64::MAX),
+            (1, i64::MIN + 1, 1, i64::MIN),
+            (2, i64::MAX / 2, 2, i64::MAX - 1),
+            (3, i64::MIN, 1, i64::MIN),
+            (5, 2, 62, 1 << 62),
+            (5, -2, 63, i64::MIN),
+            (15, i64::MAX, 0, 3_037_000_499),
+            (16, i64::MIN + 1, 0, i64::MAX),
+            (19, i64::MAX, 1, i64::MAX),
+        ];
+        for (op, a, b, expected) in in_range {
+            assert_eq!(checked_op(op, a, b), Ok(expected), "op {}", op);
+        }
+    }
+
+    #[test]
+    fn test_invalid_operands() {
+        let invalid = [
+            (3, 1, 0),
+            (4, 1, 0),
+            (5, 2, -1),
+            (9, 1, 64),
+            (10, 1, -1),
+            (15, -1, 0),
+        ];
+        for (op, a, b) in invalid {
+            assert_eq!(
+                checked_op(op, a, b),
+                Err(MathError::InvalidOperand),
+                "op {}",
+                op
+            );
+        }
+    }
+
+    #[test]
+    fn test_unsigned_ops() {
+        let cases = [
+            (0, 15, 7, 22),
+            (1, 20, 8, 12),
+            (2, 6, 4, 24),
+            (3, u64::MAX, 2, u64::MAX / 2),
+            (5, 2, 63, 1 << 63),
+            (10, u64::MAX, 63, 1),
+            (12, 3, u64::MAX, 1),
+            (18, 48, 36, 12),
+            (19, 4, 6, 12),
+        ];
+        for (op, a, b, expected) in cases {
+            assert_eq!(checked_op_unsigned(op, a, b), Ok(expected), "op {}", op);
+        }
+
+        // Going below zero is an overflow rather than a negative result
+        assert_eq!(checked_op(1, 5, 8), Ok(-3));
+        assert_eq!(checked_op_unsigned(1, 5, 8), Err(MathError::Overflow));
+        assert_eq!(
+            checked_op_unsigned(0, u64::MAX, 1),
+            Err(MathError::Overflow)
+        );
+        assert_eq!(checked_op_unsigned(5, 2, 64), Err(MathError::Overflow));
+        assert_eq!(
+            checked_op_unsigned(5, 3, u64::MAX),
+            Err(MathError::Overflow)
+        );
+        assert_eq!(checked_op_unsigned(5, 1, 64), Ok(1));
+        assert_eq!(checked_op_unsigned(3, 1, 0), Err(MathError::InvalidOperand));
+        assert_eq!(
+            checked_op_unsigned(9, 1, 64),
+            Err(MathError::InvalidOperand)
+        );
+
+        // Sign-only and unary opcodes have no unsigned form
+        assert_eq!(checked_op_unsigned(16, 5, 0), Err(MathError::UnknownOpcode));
+        assert_eq!(checked_op_unsigned(26, 5, 0), Err(MathError::UnknownOpcode));
+    }
+
+    #[test]
+    fn test_pow_ceiling() {
+        assert_eq!(checked_pow(3, i64::MAX), Err(MathError::Overflow));
+        assert_eq!(checked_pow(2, MAX_EXPONENT + 1), Err(MathError::Overflow));
+
+        // Bases 0 and ±1 never overflow, so the ceiling does not apply
+        assert_eq!(checked_pow(1, 1_000), Ok(1));
+        assert_eq!(checked_pow(-1, 1_001), Ok(-1));
+        assert_eq!(checked_pow(0, 1_000), Ok(0));
+        assert_eq!(
+            checked_pow(1, u32::MAX as i64 + 1),
+            Err(MathError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_factorial() {
+        assert_eq!(factorial(0), Ok(1));
+        assert_eq!(factorial(1), Ok(1));
+        assert_eq!(factorial(5), Ok(120));
+        assert_eq!(factorial(MAX_FACTORIAL), Ok(2_432_902_008_176_640_000));
+        assert_eq!(factorial(MAX_FACTORIAL + 1), Err(MathError::Overflow));
+        assert_eq!(factorial(i64::MAX), Err(MathError::Overflow));
+        assert_eq!(factorial(-1), Err(MathError::InvalidOperand));
+    }
+
+    #[test]
+    fn test_fibonacci() {
+        assert_eq!(fibonacci(0), Ok(0));
+        assert_eq!(fibonacci(1), Ok(1));
+        assert_eq!(fibonacci(2), Ok(1));
+        assert_eq!(fibonacci(10), Ok(55));
+        assert_eq!(fibonacci(MAX_FIBONACCI), Ok(7_540_113_804_746_346_429));
+        assert_eq!(fibonacci(MAX_FIBONACCI + 1), Err(MathError::Overflow));
+        assert_eq!(fibonacci(94), Err(MathError::Overflow));
+        assert_eq!(fibonacci(-1), Err(MathError::InvalidOperand));
+    }
+
+    #[test]
+    fn test_basis_points() {
+        assert_eq!(basis_points(10_000, 250), Ok(250));
+        assert_eq!(basis_points(200, 5_000), Ok(100));
+        assert_eq!(basis_points(-200, 5_000), Ok(-100));
+        assert_eq!(basis_points(999, 1), Ok(0));
+        assert_eq!(basis_points(7, 0), Ok(0));
+
+        // `amount * bps` would not fit in an i64 but the result does
+        assert_eq!(basis_points(i64::MAX, 5_000), Ok(i64::MAX / 2));
+        assert_eq!(basis_points(i64::MAX, BPS_DENOMINATOR), Ok(i64::MAX));
+        assert_eq!(basis_points(i64::MIN, BPS_DENOMINATOR), Ok(i64::MIN));
+        assert_eq!(
+            basis_points(i64::MAX, BPS_DENOMINATOR + 1),
+            Err(MathError::Overflow)
+        );
+        assert_eq!(basis_points(1, -1), Err(MathError::InvalidOperand));
+    }
+
+    #[test]
+    fn test_modpow() {
+        assert_eq!(modpow(2, 10, 1000), Ok(24));
+        assert_eq!(modpow(3, 0, 7), Ok(1));
+        assert_eq!(modpow(5, 3, 1), Ok(0));
+        assert_eq!(modpow(-2, 3, 5), Ok(2));
+        assert_eq!(modpow(3, 4, -5), Ok(1));
+        assert_eq!(modpow(2, 10, 0), Err(MathError::InvalidOperand));
+        assert_eq!(modpow(2, -1, 7), Err(MathError::InvalidOperand));
+        assert_eq!(modpow(2, 2, i64::MIN), Err(MathError::Overflow));
+        assert_eq!(modpow(i64::MAX - 1, 2, i64::MAX), Err(MathError::Overflow));
+    }
+
+    #[test]
+    fn test_clamped_add_and_sub() {
+        assert_eq!(clamped_add(10, 5, 100), (15, 0));
+        assert_eq!(clamped_add(90, 30, 100), (100, 20));
+        assert_eq!(
+            clamped_add(u64::MAX - 1, u64::MAX, u64::MAX),
+            (u64::MAX, u64::MAX - 1)
+        );
+        // Already past the maximum: nothing is added
+        assert_eq!(clamped_add(120, 5, 100), (120, 5));
+
+        assert_eq!(clamped_sub(10, 5, 0), (5, 0));
+        assert_eq!(clamped_sub(10, 30, 0), (0, 20));
+        assert_eq!(clamped_sub(10, 30, 4), (4, 24));
+        // Already below the minimum: nothing is taken
+        assert_eq!(clamped_sub(2, 5, 4), (2, 5));
+    }
+}
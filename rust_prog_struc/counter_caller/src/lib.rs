@@ -0,0 +1,140 @@
+use counter_program::CounterInstruction;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::invoke,
+    pubkey::Pubkey,
+};
+
+// Program entrypoint - this is where execution starts
+entrypoint!(process_instruction);
+
+/// Increment a counter by invoking the counter program
+///
+/// The counter program is passed in rather than hardcoded, so the wrapper
+/// works against whichever deployment the caller points it at. The
+/// authority's signature carries through to the inner instruction.
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account owned by the counter program
+/// 2. `[signer]` Counter authority
+/// 3. `[]` Counter program
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let counter_program = next_account_info(accounts_iter)?;
+
+    let instruction = Instruction::new_with_borsh(
+        *counter_program.key,
+        &CounterInstruction::IncrementCounter,
+        vec![
+            AccountMeta::new(*counter_account.key, false),
+            AccountMeta::new_readonly(*authority_account.key, true),
+        ],
+    );
+
+    msg!(
+        "Forwarding increment of {} to {}",
+        counter_account.key,
+        counter_program.key
+    );
+    invoke(
+        &instruction,
+        &[
+            counter_account.clone(),
+            authority_account.clone(),
+            counter_program.clone(),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use borsh::BorshDeserialize;
+    use counter_program::CounterAccount;
+    use litesvm::LiteSVM;
+    use solana_sdk::{
+        account::ReadableAccount,
+        message::Message,
+        signature::{Keypair, Signer},
+        system_program,
+        transaction::Transaction,
+    };
+
+    /// Send one instruction signed by the payer and any extra signers
+    fn send(svm: &mut LiteSVM, instruction: Instruction, payer: &Keypair, signers: &[&Keypair]) {
+        svm.expire_blockhash();
+        let message = Message::new(&[instruction], Some(&payer.pubkey()));
+        let mut all_signers = vec![payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new(&all_signers, message, svm.latest_blockhash());
+        svm.send_transaction(transaction)
+            .expect("Transaction should succeed");
+    }
+
+    #[test]
+    fn test_increment_through_cpi() {
+        let mut svm = LiteSVM::new();
+        let payer = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+
+        let counter_program_id = Pubkey::new_unique();
+        svm.add_program_from_file(
+            counter_program_id,
+            "../counter_program/target/deploy/counter_program.so",
+        )
+        .expect("Failed to load counter program");
+        let caller_program_id = Pubkey::new_unique();
+        svm.add_program_from_file(caller_program_id, "target/deploy/counter_caller.so")
+            .expect("Failed to load caller program");
+
+        let counter_keypair = Keypair::new();
+        let initialize = Instruction::new_with_borsh(
+            counter_program_id,
+            &CounterInstruction::InitializeCounter {
+                initial_value: 41,
+                step: 1,
+                min: 0,
+                max: u64::MAX,
+                saturate: false,
+                cooldown_secs: 0,
+                max_step: u64::MAX,
+            },
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        send(&mut svm, initialize, &payer, &[&counter_keypair]);
+
+        // The wrapper needs the counter program account to invoke it
+        let increment = Instruction::new_with_bytes(
+            caller_program_id,
+            &[],
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+                AccountMeta::new_readonly(counter_program_id, false),
+            ],
+        );
+        send(&mut svm, increment, &payer, &[]);
+
+        let account = svm
+            .get_account(&counter_keypair.pubkey())
+            .expect("Failed to get counter account");
+        let counter_data = CounterAccount::deserialize(&mut account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.count, 42);
+    }
+}
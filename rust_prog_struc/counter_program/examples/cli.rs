@@ -0,0 +1,235 @@
+use borsh::BorshDeserialize;
+use clap::{Parser, Subcommand};
+use counter_program::{CounterAccount, CounterInstruction};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_client::RpcClient,
+    rpc_request::RpcError,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, write_keypair_file, Keypair, Signature, Signer},
+    system_program,
+    transaction::{Transaction, TransactionError},
+};
+use std::{error::Error, path::PathBuf, time::Duration};
+
+/// Command-line tool for a deployed counter program
+#[derive(Parser)]
+struct Args {
+    /// RPC endpoint to talk to
+    #[arg(long, default_value = "http://localhost:8899")]
+    url: String,
+
+    /// Program ID of the deployed counter program
+    #[arg(long, default_value = "7t218yKLtVRFN2UqBj3UMD4kH3w6cA8cWHPFJNyC6B1u")]
+    program_id: Pubkey,
+
+    /// Payer and authority keypair [default: ~/.config/solana/id.json]
+    #[arg(long)]
+    keypair: Option<PathBuf>,
+
+    /// How many times to resend a transaction after a dropped connection or
+    /// an expired blockhash
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the current value of a counter
+    Get {
+        /// Address of the counter account
+        counter: Pubkey,
+    },
+
+    /// Create a new counter owned by the payer
+    Init {
+        /// Starting value
+        #[arg(long, default_value_t = 0)]
+        value: u64,
+
+        /// Write the new counter's keypair to this file
+        #[arg(long)]
+        save: Option<PathBuf>,
+    },
+
+    /// Increment a counter by its step
+    Increment {
+        /// Address of the counter account
+        #[arg(long)]
+        counter: Pubkey,
+    },
+}
+
+/// Delay before the first resend; each further attempt waits twice as long
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let client = RpcClient::new_with_commitment(args.url.clone(), CommitmentConfig::confirmed());
+
+    match args.command {
+        Command::Get { counter } => {
+            let counter_data = fetch_counter(&client, &args.program_id, &counter)?;
+            println!("{}", counter_data.count);
+        }
+        Command::Init { value, save } => {
+            let payer = load_payer(args.keypair)?;
+            let counter_keypair = Keypair::new();
+            println!("Counter address: {}", counter_keypair.pubkey());
+            if let Some(path) = save {
+                write_keypair_file(&counter_keypair, &path)
+                    .map_err(|err| format!("Failed to save keypair {}: {}", path.display(), err))?;
+                println!("Counter keypair saved to {}", path.display());
+            }
+
+            let instruction = Instruction::new_with_borsh(
+                args.program_id,
+                &CounterInstruction::InitializeCounter {
+                    initial_value: value,
+                    step: 1,
+                    min: 0,
+                    max: u64::MAX,
+                    saturate: false,
+                    cooldown_secs: 0,
+                    max_step: u64::MAX,
+                },
+                vec![
+                    AccountMeta::new(counter_keypair.pubkey(), true),
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            );
+            let signature = send_with_retry(
+                &client,
+                &[instruction],
+                &payer,
+                &[&counter_keypair],
+                args.retries,
+            )?;
+            println!("Transaction: {}", signature);
+
+            let counter_data = fetch_counter(&client, &args.program_id, &counter_keypair.pubkey())?;
+            println!("Count: {}", counter_data.count);
+        }
+        Command::Increment { counter } => {
+            let payer = load_payer(args.keypair)?;
+            let instruction = Instruction::new_with_borsh(
+                args.program_id,
+                &CounterInstruction::IncrementCounter,
+                vec![
+                    AccountMeta::new(counter, false),
+                    // The payer is the counter authority
+                    AccountMeta::new_readonly(payer.pubkey(), true),
+                ],
+            );
+            let signature = send_with_retry(&client, &[instruction], &payer, &[], args.retries)?;
+            println!("Transaction: {}", signature);
+
+            let counter_data = fetch_counter(&client, &args.program_id, &counter)?;
+            println!("Count: {}", counter_data.count);
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetch and decode a counter account, with a clear error when the account
+/// is missing or not a counter
+fn fetch_counter(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    counter: &Pubkey,
+) -> Result<CounterAccount, Box<dyn Error>> {
+    let Some(account) = client
+        .get_account_with_commitment(counter, client.commitment())?
+        .value
+    else {
+        return Err(format!("Counter account {} not found", counter).into());
+    };
+
+    if account.owner != *program_id {
+        return Err(format!(
+            "Account {} is owned by {}, not the counter program {}",
+            counter, account.owner, program_id
+        )
+        .into());
+    }
+
+    // Resized counters carry spare bytes after the layout
+    CounterAccount::deserialize(&mut account.data.as_slice())
+        .map_err(|err| format!("Account {} is not a counter: {}", counter, err).into())
+}
+
+/// Read the payer keypair, defaulting to the Solana CLI's wallet
+fn load_payer(path: Option<PathBuf>) -> Result<Keypair, Box<dyn Error>> {
+    let path = path.unwrap_or_else(|| {
+        let home = std::env::var_os("HOME").unwrap_or_default();
+        PathBuf::from(home).join(".config/solana/id.json")
+    });
+    read_keypair_file(&path)
+        .map_err(|err| format!("Failed to read keypair {}: {}", path.display(), err).into())
+}
+
+/// Sign and send a transaction, rebuilding it against a fresh blockhash and
+/// backing off exponentially for up to `retries` resends when the failure is
+/// recoverable
+fn send_with_retry(
+    client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+    retries: u32,
+) -> Result<Signature, Box<dyn Error>> {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        let blockhash = client.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        match client.send_and_confirm_transaction(&transaction) {
+            Err(err) if attempt < retries && is_recoverable(&err) => {
+                attempt += 1;
+                println!(
+                    "Send failed ({}), retry {}/{} in {}ms",
+                    err,
+                    attempt,
+                    retries,
+                    backoff.as_millis()
+                );
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Whether a failed send is worth repeating: dropped connections, expired
+/// blockhashes and transactions that never confirmed. A transaction the
+/// program rejected fails the same way every time.
+fn is_recoverable(err: &ClientError) -> bool {
+    match err.get_transaction_error() {
+        Some(TransactionError::BlockhashNotFound) => true,
+        Some(_) => false,
+        None => matches!(
+            err.kind(),
+            ClientErrorKind::Io(_)
+                | ClientErrorKind::Reqwest(_)
+                | ClientErrorKind::RpcError(RpcError::ForUser(_))
+        ),
+    }
+}
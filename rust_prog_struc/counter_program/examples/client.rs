@@ -1,53 +1,116 @@
-use solana_client::rpc_client::RpcClient;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::BorshDeserialize;
+use clap::Parser;
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_client::RpcClient,
+    rpc_request::RpcError,
+};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
-    signature::{Keypair, Signer},
+    signature::{Keypair, Signature, Signer},
     system_program,
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status_client_types::UiTransactionEncoding;
+use std::{
+    error::Error,
+    str::FromStr,
+    time::{Duration, Instant},
 };
-use std::str::FromStr;
-use counter_program::CounterInstruction;
+use counter_program::{CounterAccount, CounterEvent, CounterInstruction};
+
+/// Create a counter and increment it once
+#[derive(Parser)]
+struct Args {
+    /// RPC endpoint to send transactions to
+    #[arg(long, default_value = "http://localhost:8899")]
+    url: String,
+
+    /// Talk to a local test validator, shorthand for `--url
+    /// http://127.0.0.1:8899`
+    #[arg(long, conflicts_with = "url")]
+    local: bool,
+
+    /// Print the counter address and final count as one JSON object instead
+    /// of progress messages
+    #[arg(long)]
+    json: bool,
+}
+
+/// RPC endpoint of a `solana-test-validator` on this machine
+const LOCAL_URL: &str = "http://127.0.0.1:8899";
+
+impl Args {
+    /// The RPC endpoint chosen by `--url` or `--local`
+    fn rpc_url(&self) -> &str {
+        if self.local {
+            LOCAL_URL
+        } else {
+            &self.url
+        }
+    }
+}
+
+/// How long to wait for an airdrop to land before giving up
+const AIRDROP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many times to resend a transaction after a dropped connection or an
+/// expired blockhash
+const SEND_RETRIES: u32 = 3;
+
+/// Delay before the first resend; each further attempt waits twice as long
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
 
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
+
     // Replace with your actual program ID from deployment
     let program_id = Pubkey::from_str("7t218yKLtVRFN2UqBj3UMD4kH3w6cA8cWHPFJNyC6B1u")
         .expect("Invalid program ID");
 
-    // Connect to local cluster
-    let rpc_url = String::from("http://localhost:8899");
-    let client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+    let rpc_url = args.rpc_url();
+    let client = RpcClient::new_with_commitment(rpc_url.to_owned(), CommitmentConfig::confirmed());
+    if !args.json {
+        println!("Using cluster {}", rpc_url);
+    }
 
     // Generate a new keypair for paying fees
     let payer = Keypair::new();
 
     // Request airdrop of 1 SOL for transaction fees
-    println!("Requesting airdrop...");
     let airdrop_signature = client
         .request_airdrop(&payer.pubkey(), 1_000_000_000)
         .expect("Failed to request airdrop");
+    if !args.json {
+        println!("Airdrop: {}", airdrop_signature);
+    }
 
     // Wait for airdrop confirmation
-    loop {
-        if client
-            .confirm_transaction(&airdrop_signature)
-            .unwrap_or(false)
-        {
-            break;
-        }
-        std::thread::sleep(std::time::Duration::from_millis(500));
+    confirm_airdrop(&client, &payer.pubkey(), 1_000_000_000).expect("Airdrop was not confirmed");
+    if !args.json {
+        println!("Airdrop confirmed");
+        println!("\nInitializing counter...");
     }
-    println!("Airdrop confirmed");
 
-    println!("\nInitializing counter...");
     let counter_keypair = Keypair::new();
     let initial_value = 100u64;
 
     // Serialize the initialize instruction data
-    let instruction_data = borsh::to_vec(&CounterInstruction::InitializeCounter { initial_value })
-        .expect("Failed to serialize instruction");
+    let instruction_data = borsh::to_vec(&CounterInstruction::InitializeCounter {
+        initial_value,
+        step: 1,
+        min: 0,
+        max: u64::MAX,
+        saturate: false,
+        cooldown_secs: 0,
+        max_step: u64::MAX,
+    })
+    .expect("Failed to serialize instruction");
 
     let initialize_instruction = Instruction::new_with_bytes(
         program_id,
@@ -59,27 +122,28 @@ async fn main() {
         ],
     );
 
-    let mut transaction =
-        Transaction::new_with_payer(&[initialize_instruction], Some(&payer.pubkey()));
-
-    let blockhash = client
-        .get_latest_blockhash()
-        .expect("Failed to get blockhash");
-    transaction.sign(&[&payer, &counter_keypair], blockhash);
-
-    match client.send_and_confirm_transaction(&transaction) {
-        Ok(signature) => {
+    match send_with_retry(
+        &client,
+        &[initialize_instruction],
+        &payer,
+        &[&counter_keypair],
+        SEND_RETRIES,
+    ) {
+        Ok(signature) if !args.json => {
             println!("Counter initialized!");
             println!("Transaction: {}", signature);
             println!("Counter address: {}", counter_keypair.pubkey());
         }
+        Ok(_) => {}
         Err(err) => {
             eprintln!("Failed to initialize counter: {}", err);
             return;
         }
     }
 
-    println!("\nIncrementing counter...");
+    if !args.json {
+        println!("\nIncrementing counter...");
+    }
     // Serialize the increment instruction data
     let increment_data = borsh::to_vec(&CounterInstruction::IncrementCounter)
         .expect("Failed to serialize instruction");
@@ -87,21 +151,196 @@ async fn main() {
     let increment_instruction = Instruction::new_with_bytes(
         program_id,
         &increment_data,
-        vec![AccountMeta::new(counter_keypair.pubkey(), true)],
+        vec![
+            AccountMeta::new(counter_keypair.pubkey(), true),
+            // The payer is the counter authority
+            AccountMeta::new_readonly(payer.pubkey(), true),
+        ],
     );
 
-    let mut transaction =
-        Transaction::new_with_payer(&[increment_instruction], Some(&payer.pubkey()));
-
-    transaction.sign(&[&payer, &counter_keypair], blockhash);
-
-    match client.send_and_confirm_transaction(&transaction) {
+    match send_with_retry(
+        &client,
+        &[increment_instruction],
+        &payer,
+        &[&counter_keypair],
+        SEND_RETRIES,
+    ) {
+        Ok(signature) if args.json => {
+            let counter = counter_keypair.pubkey();
+            match fetch_count(&client, &counter) {
+                Ok(count) => println!("{}", counter_json(&counter, count, &signature)),
+                Err(err) => eprintln!("Failed to read counter: {}", err),
+            }
+        }
         Ok(signature) => {
             println!("Counter incremented!");
             println!("Transaction: {}", signature);
+            print_counter_events(&client, &signature);
         }
         Err(err) => {
             eprintln!("Failed to increment counter: {}", err);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Read the current value of a counter account
+fn fetch_count(client: &RpcClient, counter: &Pubkey) -> Result<u64, Box<dyn Error>> {
+    let account = client.get_account(counter)?;
+    Ok(CounterAccount::deserialize(&mut account.data.as_slice())?.count)
+}
+
+/// The counter a run created as a JSON object, e.g.
+/// `{"counter":"...","count":101,"signature":"..."}`, where the signature is
+/// the final increment's
+fn counter_json(counter: &Pubkey, count: u64, signature: &Signature) -> Value {
+    json!({
+        "counter": counter.to_string(),
+        "count": count,
+        "signature": signature.to_string(),
+    })
+}
+
+/// Poll the payer's balance until it reflects the airdrop, giving up after
+/// `AIRDROP_TIMEOUT` instead of guessing with a fixed sleep
+fn confirm_airdrop(
+    client: &RpcClient,
+    payer: &Pubkey,
+    lamports: u64,
+) -> Result<(), Box<dyn Error>> {
+    let deadline = Instant::now() + AIRDROP_TIMEOUT;
+    while client.get_balance(payer).unwrap_or(0) < lamports {
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "Airdrop of {} lamports to {} not confirmed after {}s",
+                lamports,
+                payer,
+                AIRDROP_TIMEOUT.as_secs()
+            )
+            .into());
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+    Ok(())
+}
+
+/// Sign and send a transaction, rebuilding it against a fresh blockhash and
+/// backing off exponentially for up to `retries` resends when the failure is
+/// recoverable
+fn send_with_retry(
+    client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+    retries: u32,
+) -> Result<Signature, Box<dyn Error>> {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        let blockhash = client.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        match client.send_and_confirm_transaction(&transaction) {
+            Err(err) if attempt < retries && is_recoverable(&err) => {
+                attempt += 1;
+                // Progress goes to stderr so it never mixes with JSON output
+                eprintln!(
+                    "Send failed ({}), retry {}/{} in {}ms",
+                    err,
+                    attempt,
+                    retries,
+                    backoff.as_millis()
+                );
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Whether a failed send is worth repeating: dropped connections, expired
+/// blockhashes and transactions that never confirmed. A transaction the
+/// program rejected fails the same way every time.
+fn is_recoverable(err: &ClientError) -> bool {
+    match err.get_transaction_error() {
+        Some(TransactionError::BlockhashNotFound) => true,
+        Some(_) => false,
+        None => matches!(
+            err.kind(),
+            ClientErrorKind::Io(_)
+                | ClientErrorKind::Reqwest(_)
+                | ClientErrorKind::RpcError(RpcError::ForUser(_))
+        ),
+    }
+}
+
+/// Fetch a confirmed transaction and decode the `CounterEvent`s the program
+/// emitted as base64 `Program data:` log lines
+fn print_counter_events(client: &RpcClient, signature: &Signature) {
+    let transaction = match client.get_transaction(signature, UiTransactionEncoding::Json) {
+        Ok(transaction) => transaction,
+        Err(err) => {
+            eprintln!("Failed to fetch transaction logs: {}", err);
+            return;
+        }
+    };
+
+    let logs: Option<Vec<String>> = transaction
+        .transaction
+        .meta
+        .and_then(|meta| meta.log_messages.into());
+
+    for line in logs.unwrap_or_default() {
+        // Each `sol_log_data` field is base64-encoded and space separated
+        let Some(data) = line.strip_prefix("Program data: ") else {
+            continue;
+        };
+        for field in data.split(' ') {
+            let event = STANDARD
+                .decode(field)
+                .ok()
+                .and_then(|bytes| CounterEvent::try_from_slice(&bytes).ok());
+            if let Some(event) = event {
+                println!("Event: {:?}", event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_local_url_preset() {
+        let args = Args::try_parse_from(["client", "--local"]).expect("--local should parse");
+        assert_eq!(args.rpc_url(), LOCAL_URL);
+
+        let args = Args::try_parse_from(["client", "--url", "https://api.devnet.solana.com"])
+            .expect("--url should parse");
+        assert_eq!(args.rpc_url(), "https://api.devnet.solana.com");
+
+        // A preset and an explicit endpoint contradict each other
+        assert!(Args::try_parse_from(["client", "--local", "--url", "http://x"]).is_err());
+    }
+
+    #[test]
+    fn test_counter_json() {
+        let counter = Pubkey::new_unique();
+        let signature = Signature::default();
+        assert_eq!(
+            counter_json(&counter, 101, &signature).to_string(),
+            format!(
+                r#"{{"counter":"{}","count":101,"signature":"{}"}}"#,
+                counter, signature
+            )
+        );
+    }
+}
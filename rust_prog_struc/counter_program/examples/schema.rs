@@ -0,0 +1,104 @@
+use borsh::{
+    schema::{BorshSchemaContainer, Definition, Fields},
+    BorshSchema,
+};
+use counter_program::{CounterAccount, CounterInstruction};
+
+fn main() {
+    println!("{{");
+    print_schema::<CounterAccount>();
+    println!(",");
+    print_schema::<CounterInstruction>();
+    println!("\n}}");
+}
+
+/// Print the Borsh layout of `T` and every type it contains as a JSON
+/// object keyed by type name, along with the largest encoding of `T`
+fn print_schema<T: BorshSchema>() {
+    let schema = BorshSchemaContainer::for_type::<T>();
+    let max_size = match borsh::max_serialized_size::<T>() {
+        Ok(size) => size.to_string(),
+        Err(_) => String::from("null"),
+    };
+
+    println!("  \"{}\": {{", schema.declaration());
+    println!("    \"max_size\": {},", max_size);
+    println!("    \"definitions\": {{");
+    let definitions: Vec<String> = schema
+        .definitions()
+        .map(|(declaration, definition)| {
+            format!("      \"{}\": {}", declaration, render(definition))
+        })
+        .collect();
+    println!("{}", definitions.join(",\n"));
+    println!("    }}");
+    print!("  }}");
+}
+
+/// Render one definition as a JSON value, listing fields and variants in
+/// wire order
+fn render(definition: &Definition) -> String {
+    match definition {
+        Definition::Primitive(size) => format!("{{ \"primitive\": {} }}", size),
+        // A zero length width means the length is fixed and not encoded
+        Definition::Sequence {
+            length_width: Definition::ARRAY_LENGTH_WIDTH,
+            length_range,
+            elements,
+        } => format!(
+            "{{ \"array\": \"{}\", \"length\": {} }}",
+            elements,
+            length_range.start()
+        ),
+        Definition::Sequence {
+            length_width,
+            elements,
+            ..
+        } => format!(
+            "{{ \"vec\": \"{}\", \"length_width\": {} }}",
+            elements, length_width
+        ),
+        Definition::Tuple { elements } => {
+            let elements: Vec<String> = elements.iter().map(|e| format!("\"{}\"", e)).collect();
+            format!("{{ \"tuple\": [{}] }}", elements.join(", "))
+        }
+        Definition::Enum {
+            tag_width,
+            variants,
+        } => {
+            let variants: Vec<String> = variants
+                .iter()
+                .map(|(tag, name, declaration)| {
+                    format!(
+                        "{{ \"tag\": {}, \"name\": \"{}\", \"type\": \"{}\" }}",
+                        tag, name, declaration
+                    )
+                })
+                .collect();
+            format!(
+                "{{ \"tag_width\": {}, \"enum\": [{}] }}",
+                tag_width,
+                variants.join(", ")
+            )
+        }
+        Definition::Struct { fields } => {
+            let fields: Vec<String> = match fields {
+                Fields::NamedFields(fields) => fields
+                    .iter()
+                    .map(|(name, declaration)| {
+                        format!(
+                            "{{ \"name\": \"{}\", \"type\": \"{}\" }}",
+                            name, declaration
+                        )
+                    })
+                    .collect(),
+                Fields::UnnamedFields(fields) => fields
+                    .iter()
+                    .map(|declaration| format!("{{ \"type\": \"{}\" }}", declaration))
+                    .collect(),
+                Fields::Empty => Vec::new(),
+            };
+            format!("{{ \"struct\": [{}] }}", fields.join(", "))
+        }
+    }
+}
@@ -1,18 +1,54 @@
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use first_solana_math::{clamped_add, clamped_sub};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    entrypoint,
+    compute_units::sol_remaining_compute_units,
     entrypoint::ProgramResult,
+    hash::hashv,
+    instruction::Instruction,
+    log::sol_log_data,
     msg,
-    program::invoke,
+    program::{get_return_data, invoke, invoke_signed, set_return_data, MAX_RETURN_DATA},
     program_error::ProgramError,
     pubkey::Pubkey,
-    system_instruction,
-    sysvar::{rent::Rent, Sysvar},
+    system_instruction, system_program,
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 
-// Program entrypoint - this is where execution starts
-entrypoint!(process_instruction);
+// Program entrypoint - this is where execution starts. Programs that CPI into
+// the counter build it with `no-entrypoint` so only their own entrypoint exists.
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+/// Seed prefix for counter PDAs
+pub const COUNTER_SEED: &[u8] = b"counter";
+
+/// Derive the PDA counter address and bump for an authority
+pub fn find_counter_address(authority: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COUNTER_SEED, authority.as_ref()], program_id)
+}
+
+/// Derive the address and bump of an authority's counter with the given name
+pub fn find_named_counter_address(
+    authority: &Pubkey,
+    name: &[u8; NAME_LEN],
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COUNTER_SEED, authority.as_ref(), name], program_id)
+}
+
+/// Merkle leaf committing to a counter value: the SHA-256 of its
+/// little-endian bytes
+pub fn count_leaf(count: u64) -> [u8; 32] {
+    hashv(&[&count.to_le_bytes()]).to_bytes()
+}
+
+/// Merkle parent of two nodes. The pair is hashed smaller first, so proofs
+/// need no left or right markers.
+pub fn merkle_parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[first, second]).to_bytes()
+}
 
 /// Main instruction processing function
 /// Routes incoming instructions to appropriate handlers
@@ -21,53 +57,613 @@ pub fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
+    // The first byte selects the instruction variant
+    let Some(&tag) = instruction_data.first() else {
+        msg!("Instruction data is empty: expected a 1-byte variant tag followed by its fields");
+        return Err(CounterError::InvalidInstruction.into());
+    };
+
     // Parse instruction data
-    let instruction = CounterInstruction::try_from_slice(instruction_data)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let instruction = CounterInstruction::try_from_slice(instruction_data).map_err(|_| {
+        match CounterInstruction::min_len(tag) {
+            Some(min_len) => msg!(
+                "Invalid data for instruction {}: got {} bytes, expected at least {}",
+                tag,
+                instruction_data.len(),
+                min_len
+            ),
+            None => msg!("Unknown instruction variant {}", tag),
+        }
+        CounterError::InvalidInstruction
+    })?;
 
     // Route to appropriate handler
     match instruction {
-        CounterInstruction::InitializeCounter { initial_value } => {
+        CounterInstruction::InitializeCounter {
+            initial_value,
+            step,
+            min,
+            max,
+            saturate,
+            cooldown_secs,
+            max_step,
+        } => {
             msg!("Instruction: Initialize Counter");
-            process_initialize_counter(program_id, accounts, initial_value)?
+            let settings = CounterSettings {
+                step,
+                min,
+                max,
+                saturate,
+                cooldown_secs,
+                max_step,
+            };
+            process_initialize_counter(program_id, accounts, initial_value, settings)?
         }
         CounterInstruction::IncrementCounter => {
             msg!("Instruction: Increment Counter");
             process_increment_counter(program_id, accounts)?
         }
+        CounterInstruction::DecrementCounter => {
+            msg!("Instruction: Decrement Counter");
+            process_decrement_counter(program_id, accounts)?
+        }
+        CounterInstruction::SetCounter { value } => {
+            msg!("Instruction: Set Counter");
+            process_set_counter(program_id, accounts, value)?
+        }
+        CounterInstruction::Reset => {
+            msg!("Instruction: Reset Counter");
+            process_reset_counter(program_id, accounts)?
+        }
+        CounterInstruction::AddToCounter { amount } => {
+            msg!("Instruction: Add To Counter");
+            process_add_to_counter(program_id, accounts, amount)?
+        }
+        CounterInstruction::SubtractFromCounter { amount } => {
+            msg!("Instruction: Subtract From Counter");
+            process_subtract_from_counter(program_id, accounts, amount)?
+        }
+        CounterInstruction::TransferAuthority { new_authority } => {
+            msg!("Instruction: Transfer Authority");
+            process_transfer_authority(program_id, accounts, new_authority)?
+        }
+        CounterInstruction::CloseCounter => {
+            msg!("Instruction: Close Counter");
+            process_close_counter(program_id, accounts)?
+        }
+        CounterInstruction::InitializePdaCounter { initial_value } => {
+            msg!("Instruction: Initialize PDA Counter");
+            process_initialize_pda_counter(program_id, accounts, initial_value, None)?
+        }
+        CounterInstruction::GetCounter => {
+            msg!("Instruction: Get Counter");
+            process_get_counter(program_id, accounts)?
+        }
+        CounterInstruction::InitializeOrGet { initial_value } => {
+            msg!("Instruction: Initialize Or Get Counter");
+            process_initialize_or_get(program_id, accounts, initial_value)?
+        }
+        CounterInstruction::InitializeWideCounter { initial_value } => {
+            msg!("Instruction: Initialize Wide Counter");
+            process_initialize_wide_counter(program_id, accounts, initial_value)?
+        }
+        CounterInstruction::IncrementWideCounter => {
+            msg!("Instruction: Increment Wide Counter");
+            process_increment_wide_counter(program_id, accounts)?
+        }
+        CounterInstruction::Migrate => {
+            msg!("Instruction: Migrate Counter");
+            process_migrate(program_id, accounts)?
+        }
+        CounterInstruction::IncrementBy { times } => {
+            msg!("Instruction: Increment By");
+            process_increment_by(program_id, accounts, times)?
+        }
+        CounterInstruction::InitMulti => {
+            msg!("Instruction: Initialize Multi Counter");
+            process_init_multi(program_id, accounts)?
+        }
+        CounterInstruction::IncrementId { id } => {
+            msg!("Instruction: Increment Id");
+            process_update_multi(program_id, accounts, id, None)?
+        }
+        CounterInstruction::SetId { id, value } => {
+            msg!("Instruction: Set Id");
+            process_update_multi(program_id, accounts, id, Some(value))?
+        }
+        CounterInstruction::GetBump => {
+            msg!("Instruction: Get Bump");
+            process_get_bump(program_id, accounts)?
+        }
+        CounterInstruction::InitializeMultisig { signers, threshold } => {
+            msg!("Instruction: Initialize Multisig");
+            process_initialize_multisig(program_id, accounts, signers, threshold)?
+        }
+        CounterInstruction::Resize { new_len } => {
+            msg!("Instruction: Resize Counter");
+            process_resize(program_id, accounts, new_len)?
+        }
+        CounterInstruction::GetHistory => {
+            msg!("Instruction: Get History");
+            process_get_history(program_id, accounts)?
+        }
+        CounterInstruction::InitializeSignedCounter { initial_value } => {
+            msg!("Instruction: Initialize Signed Counter");
+            process_initialize_signed_counter(program_id, accounts, initial_value)?
+        }
+        CounterInstruction::IncrementSignedCounter => {
+            msg!("Instruction: Increment Signed Counter");
+            process_update_signed_counter(program_id, accounts, 1)?
+        }
+        CounterInstruction::DecrementSignedCounter => {
+            msg!("Instruction: Decrement Signed Counter");
+            process_update_signed_counter(program_id, accounts, -1)?
+        }
+        CounterInstruction::MultiplyCounter { factor } => {
+            msg!("Instruction: Multiply Counter");
+            process_multiply_counter(program_id, accounts, factor)?
+        }
+        CounterInstruction::DoubleCounter => {
+            msg!("Instruction: Double Counter");
+            process_multiply_counter(program_id, accounts, 2)?
+        }
+        CounterInstruction::InitializeAndIncrement { initial_value } => {
+            msg!("Instruction: Initialize And Increment Counter");
+            process_initialize_and_increment(program_id, accounts, initial_value)?
+        }
+        CounterInstruction::AddCheckedWithOverflowReturn { amount } => {
+            msg!("Instruction: Add With Overflow Return");
+            process_add_with_overflow_return(program_id, accounts, amount)?
+        }
+        CounterInstruction::CompareAndSet { expected, new } => {
+            msg!("Instruction: Compare And Set Counter");
+            process_compare_and_set(program_id, accounts, expected, new)?
+        }
+        CounterInstruction::Pause => {
+            msg!("Instruction: Pause Counter");
+            process_set_paused(program_id, accounts, true)?
+        }
+        CounterInstruction::Unpause => {
+            msg!("Instruction: Unpause Counter");
+            process_set_paused(program_id, accounts, false)?
+        }
+        CounterInstruction::TransferValue { amount } => {
+            msg!("Instruction: Transfer Counter Value");
+            process_transfer_value(program_id, accounts, amount)?
+        }
+        CounterInstruction::BatchInitialize { values } => {
+            msg!("Instruction: Batch Initialize Counters");
+            process_batch_initialize(program_id, accounts, &values)?
+        }
+        CounterInstruction::SubtractWithDeficitReturn { amount } => {
+            msg!("Instruction: Subtract With Deficit Return");
+            process_subtract_with_deficit_return(program_id, accounts, amount)?
+        }
+        CounterInstruction::SetFromReturnData { source, data } => {
+            msg!("Instruction: Set Counter From Return Data");
+            process_set_from_return_data(program_id, accounts, source, data)?
+        }
+        CounterInstruction::Snapshot => {
+            msg!("Instruction: Snapshot Counter");
+            process_snapshot(program_id, accounts)?
+        }
+        CounterInstruction::Restore => {
+            msg!("Instruction: Restore Counter");
+            process_restore(program_id, accounts)?
+        }
+        CounterInstruction::InitializeMeanCounter => {
+            msg!("Instruction: Initialize Mean Counter");
+            process_initialize_mean_counter(program_id, accounts)?
+        }
+        CounterInstruction::AddSample { value } => {
+            msg!("Instruction: Add Sample");
+            process_add_sample(program_id, accounts, value)?
+        }
+        CounterInstruction::GetMean => {
+            msg!("Instruction: Get Mean");
+            process_get_mean(program_id, accounts)?
+        }
+        CounterInstruction::GetParity => {
+            msg!("Instruction: Get Parity");
+            process_get_parity(program_id, accounts)?
+        }
+        CounterInstruction::GetExtremes => {
+            msg!("Instruction: Get Extremes");
+            process_get_extremes(program_id, accounts)?
+        }
+        CounterInstruction::InitializeNamedPdaCounter {
+            initial_value,
+            name,
+        } => {
+            msg!("Instruction: Initialize Named PDA Counter");
+            check_name(&name)?;
+            process_initialize_pda_counter(program_id, accounts, initial_value, Some(name))?
+        }
+        CounterInstruction::GetName => {
+            msg!("Instruction: Get Name");
+            process_get_name(program_id, accounts)?
+        }
+        CounterInstruction::GetCounters => {
+            msg!("Instruction: Get Counters");
+            process_get_counters(program_id, accounts)?
+        }
+        CounterInstruction::VerifyCountProof { proof, root } => {
+            msg!("Instruction: Verify Count Proof");
+            process_verify_count_proof(program_id, accounts, &proof, &root)?
+        }
     };
 
     Ok(())
 }
 
 /// Instructions supported by the counter program
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+///
+/// Borsh tags each variant with its position, so new variants must be
+/// appended: inserting or reordering would change the tag of every variant
+/// after it and break clients already deployed against the old layout.
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug)]
 pub enum CounterInstruction {
-    /// Initialize a new counter with the given value
-    InitializeCounter { initial_value: u64 },
+    /// Initialize a new counter with the given value, increment step and
+    /// inclusive `min..=max` bounds. When `saturate` is set, increments and
+    /// decrements clamp at the bounds instead of failing. Instructions that
+    /// raise the count must be at least `cooldown_secs` apart, 0 for no
+    /// limit, and a single call may add or subtract at most `max_step`. The
+    /// payer becomes the authority unless an authority account is passed
+    /// after the System Program.
+    InitializeCounter {
+        initial_value: u64,
+        step: u64,
+        min: u64,
+        max: u64,
+        saturate: bool,
+        cooldown_secs: i64,
+        max_step: u64,
+    },
 
-    /// Increment an existing counter by 1
+    /// Increment an existing counter by its configured step
     IncrementCounter,
+
+    /// Decrement an existing counter by 1. The counter's `min` acts as its
+    /// floor: decrements below it fail, or clamp to it when `saturate` is set.
+    DecrementCounter,
+
+    /// Overwrite an existing counter with the given value
+    SetCounter { value: u64 },
+
+    /// Reset an existing counter back to 0
+    Reset,
+
+    /// Add the given amount to an existing counter
+    AddToCounter { amount: u64 },
+
+    /// Subtract the given amount from an existing counter
+    SubtractFromCounter { amount: u64 },
+
+    /// Hand control of an existing counter to a new authority, which may be
+    /// a multisig account created with `InitializeMultisig`
+    TransferAuthority { new_authority: Pubkey },
+
+    /// Close an existing counter and reclaim its rent lamports
+    CloseCounter,
+
+    /// Initialize a new counter at the PDA derived from the authority
+    InitializePdaCounter { initial_value: u64 },
+
+    /// Read the current value without mutating it, returned as
+    /// little-endian `u64` return data
+    GetCounter,
+
+    /// Initialize a new counter with default settings, or succeed without
+    /// changes if the counter already exists
+    InitializeOrGet { initial_value: u64 },
+
+    /// Initialize a new `u128` counter for values that may exceed `u64::MAX`
+    InitializeWideCounter { initial_value: u128 },
+
+    /// Increment an existing `u128` counter by 1
+    IncrementWideCounter,
+
+    /// Upgrade a counter stored in an older layout to the current version
+    Migrate,
+
+    /// Increment an existing counter `times` times in one instruction
+    IncrementBy { times: u32 },
+
+    /// Create an empty account holding several counters keyed by id
+    InitMulti,
+
+    /// Increment the counter with `id` in a multi-counter, adding it if new
+    IncrementId { id: u32 },
+
+    /// Overwrite the counter with `id` in a multi-counter, adding it if new
+    SetId { id: u32, value: u64 },
+
+    /// Read the PDA bump stored with a counter, returned as single-byte
+    /// return data. Counters not created at a PDA store 0.
+    GetBump,
+
+    /// Create a shared authority that approves mutations signed by at least
+    /// `threshold` of `signers`
+    InitializeMultisig { signers: Vec<Pubkey>, threshold: u8 },
+
+    /// Reallocate a counter account to `new_len` bytes, keeping it
+    /// rent-exempt. Accounts never shrink below the current layout.
+    Resize { new_len: u64 },
+
+    /// Read the values after the last `HISTORY_LEN` mutations, oldest first,
+    /// returned as little-endian `u64`s
+    GetHistory,
+
+    /// Initialize a new `i64` counter that may go below zero
+    InitializeSignedCounter { initial_value: i64 },
+
+    /// Increment an existing signed counter by 1
+    IncrementSignedCounter,
+
+    /// Decrement an existing signed counter by 1, crossing zero if needed
+    DecrementSignedCounter,
+
+    /// Multiply an existing counter by the given factor, within its bounds
+    MultiplyCounter { factor: u64 },
+
+    /// Multiply an existing counter by 2
+    DoubleCounter,
+
+    /// Initialize a new counter with default settings and increment it once,
+    /// leaving it at `initial_value + 1`
+    InitializeAndIncrement { initial_value: u64 },
+
+    /// Add the given amount, clamping at the counter maximum instead of
+    /// failing. The part of `amount` that did not fit is returned as
+    /// little-endian `u64` return data, 0 when nothing was clamped.
+    AddCheckedWithOverflowReturn { amount: u64 },
+
+    /// Set the counter to `new` only if it currently holds `expected`.
+    /// Otherwise fails with `CounterError::CasFailed`, returning the current
+    /// value as little-endian `u64` return data.
+    CompareAndSet { expected: u64, new: u64 },
+
+    /// Freeze the counter value: increments, decrements, sets and the other
+    /// value changes fail with `CounterError::Paused` until `Unpause`. Reads,
+    /// authority transfers and closing still work.
+    Pause,
+
+    /// Let a paused counter change again
+    Unpause,
+
+    /// Move `amount` from one counter to another in a single instruction.
+    /// Both counters must share the signing authority.
+    TransferValue { amount: u64 },
+
+    /// Create one default counter per value, all funded by and belonging to
+    /// the payer. The counter accounts follow the payer and System Program,
+    /// one for each value and in the same order. At most
+    /// `MAX_BATCH_COUNTERS` values are accepted.
+    BatchInitialize { values: Vec<u64> },
+
+    /// Subtract the given amount, clamping at the counter minimum instead of
+    /// failing. The part of `amount` that could not be taken is returned as
+    /// little-endian `u64` return data, 0 when nothing was clamped.
+    SubtractWithDeficitReturn { amount: u64 },
+
+    /// Invoke the `source` program with `data` and store the little-endian
+    /// `u64` it returns as the new count. The return data must be exactly 8
+    /// bytes and set by `source` itself.
+    SetFromReturnData { source: Pubkey, data: Vec<u8> },
+
+    /// Copy the counter's state into a snapshot account along with the
+    /// current time. A new snapshot account must sign and is paid for by the
+    /// authority; an existing one is overwritten.
+    Snapshot,
+
+    /// Write a snapshot's value back into the counter. The snapshot must
+    /// have been taken under the counter's current authority.
+    Restore,
+
+    /// Initialize a new mean counter with no samples
+    InitializeMeanCounter,
+
+    /// Add one sample to an existing mean counter
+    AddSample { value: u64 },
+
+    /// Read the mean of a mean counter's samples, rounded down, returned as
+    /// little-endian `u64` return data
+    GetMean,
+
+    /// Read whether the count is odd, returned as a single byte: 0 for even,
+    /// 1 for odd
+    GetParity,
+
+    /// Read the highest then the lowest value the counter has held, returned
+    /// as two little-endian `u64`s
+    GetExtremes,
+
+    /// Initialize a new counter at the PDA derived from the authority and
+    /// `name`, so one authority can hold several named counters. The name is
+    /// UTF-8 padded with trailing zero bytes and must not be empty.
+    InitializeNamedPdaCounter {
+        initial_value: u64,
+        name: [u8; NAME_LEN],
+    },
+
+    /// Read the counter's name without its zero padding, returned as raw
+    /// return data. Unnamed counters return no bytes.
+    GetName,
+
+    /// Read the values of every counter passed, returned as a little-endian
+    /// `u32` count followed by one little-endian `u64` per initialized
+    /// counter, in account order. Uninitialized accounts are skipped and
+    /// logged.
+    GetCounters,
+
+    /// Check that the counter's current value is a leaf of the Merkle tree
+    /// with `root`, hashing `count_leaf` up through the `proof` siblings with
+    /// `merkle_parent`. Returns one byte: 1 if the proof holds, 0 otherwise.
+    VerifyCountProof {
+        proof: Vec<[u8; 32]>,
+        root: [u8; 32],
+    },
+}
+
+impl CounterInstruction {
+    /// Minimum Borsh-encoded length, including the 1-byte tag, of the
+    /// variant with the given tag, or `None` for unknown tags
+    pub fn min_len(tag: u8) -> Option<usize> {
+        let fields_len = match tag {
+            // InitializeCounter: initial_value, step, min, max, saturate,
+            // cooldown_secs, max_step
+            0 => 8 + 8 + 8 + 8 + 1 + 8 + 8,
+            // IncrementCounter, DecrementCounter
+            1 | 2 => 0,
+            // SetCounter: value
+            3 => 8,
+            // Reset
+            4 => 0,
+            // AddToCounter, SubtractFromCounter: amount
+            5 | 6 => 8,
+            // TransferAuthority: new_authority
+            7 => 32,
+            // CloseCounter
+            8 => 0,
+            // InitializePdaCounter: initial_value
+            9 => 8,
+            // GetCounter
+            10 => 0,
+            // InitializeOrGet: initial_value
+            11 => 8,
+            // InitializeWideCounter: initial_value
+            12 => 16,
+            // IncrementWideCounter
+            13 => 0,
+            // Migrate
+            14 => 0,
+            // IncrementBy: times
+            15 => 4,
+            // InitMulti
+            16 => 0,
+            // IncrementId: id
+            17 => 4,
+            // SetId: id, value
+            18 => 4 + 8,
+            // GetBump
+            19 => 0,
+            // InitializeMultisig: empty signers vector, threshold
+            20 => 4 + 1,
+            // Resize: new_len
+            21 => 8,
+            // GetHistory
+            22 => 0,
+            // InitializeSignedCounter: initial_value
+            23 => 8,
+            // IncrementSignedCounter, DecrementSignedCounter
+            24 | 25 => 0,
+            // MultiplyCounter: factor
+            26 => 8,
+            // DoubleCounter
+            27 => 0,
+            // InitializeAndIncrement: initial_value
+            28 => 8,
+            // AddCheckedWithOverflowReturn: amount
+            29 => 8,
+            // CompareAndSet: expected, new
+            30 => 8 + 8,
+            // Pause, Unpause
+            31 | 32 => 0,
+            // TransferValue: amount
+            33 => 8,
+            // BatchInitialize: empty values vector
+            34 => 4,
+            // SubtractWithDeficitReturn: amount
+            35 => 8,
+            // SetFromReturnData: source, empty data vector
+            36 => 32 + 4,
+            // Snapshot, Restore, InitializeMeanCounter
+            37..=39 => 0,
+            // AddSample: value
+            40 => 8,
+            // GetMean, GetParity, GetExtremes
+            41..=43 => 0,
+            // InitializeNamedPdaCounter: initial_value, name
+            44 => 8 + NAME_LEN,
+            // GetName, GetCounters
+            45 | 46 => 0,
+            // VerifyCountProof: empty proof vector, root
+            47 => 4 + 32,
+            _ => return None,
+        };
+        Some(1 + fields_len)
+    }
 }
 
 /// Initialize a new counter account
 ///
 /// Accounts expected:
 /// 1. `[signer, writable]` Counter account to create
-/// 2. `[signer, writable]` Payer account
+/// 2. `[signer, writable]` Payer account, which funds the counter
 /// 3. `[]` System Program
+/// 4. `[]` Optional counter authority, defaulting to the payer
 fn process_initialize_counter(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     initial_value: u64,
+    settings: CounterSettings,
 ) -> ProgramResult {
+    let CounterSettings {
+        step,
+        min,
+        max,
+        saturate,
+        cooldown_secs,
+        max_step,
+    } = settings;
+
+    check_account_count(accounts, CREATE_ACCOUNTS)?;
     let accounts_iter = &mut accounts.iter();
 
     let counter_account = next_account_info(accounts_iter)?;
     let payer_account = next_account_info(accounts_iter)?;
     let system_program = next_account_info(accounts_iter)?;
+    // A separate authority lets e.g. a treasury pay for a user's counter
+    let authority = next_account_info(accounts_iter).map_or(*payer_account.key, |a| *a.key);
+    check_system_program(system_program)?;
 
-    let account_space = 8;
+    // create_account would fail on an existing account, so say why up front
+    if counter_account.owner == program_id || !counter_account.data_is_empty() {
+        msg!(
+            "Counter account {} is already initialized",
+            counter_account.key
+        );
+        return Err(CounterError::AlreadyInitialized.into());
+    }
+
+    // A zero step would turn every increment into a no-op
+    if step == 0 {
+        msg!("Counter step must be non-zero");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if !(min..=max).contains(&initial_value) {
+        msg!(
+            "Initial value {} must lie within the bounds {}..={}",
+            initial_value,
+            min,
+            max
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if cooldown_secs < 0 {
+        msg!("Counter cooldown must not be negative");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Nobody can sign as the System Program, so the counter would be frozen
+    if authority == system_program::id() {
+        msg!("Counter authority cannot be the System Program");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let account_space = CounterAccount::LEN;
     let rent = Rent::get()?;
     let required_lamports = rent.minimum_balance(account_space);
 
@@ -86,182 +682,7033 @@ fn process_initialize_counter(
             system_program.clone(),
         ],
     )?;
+    check_rent_exempt(counter_account, account_space)?;
+
+    // Show where the payer's lamports went
+    msg!(
+        "Allocated {} bytes for {} lamports of rent, payer has {} lamports left",
+        account_space,
+        required_lamports,
+        payer_account.lamports()
+    );
 
     // Initialize counter data
     let counter_data = CounterAccount {
-        count: initial_value,
+        step,
+        min,
+        max,
+        saturate,
+        cooldown_secs,
+        max_step,
+        ..CounterAccount::new(initial_value, authority)?
     };
 
     let mut account_data = &mut counter_account.data.borrow_mut()[..];
     counter_data.serialize(&mut account_data)?;
 
-    msg!("Counter initialized with value: {}", initial_value);
+    msg!(
+        "Counter initialized with value: {} and step {} (update {} at {})",
+        initial_value,
+        counter_data.step,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Initialized {
+        value: counter_data.count,
+    }
+    .emit()?;
 
     Ok(())
 }
 
-/// Increment an existing counter
+/// Initialize a counter unless it already exists
+///
+/// An account that is already owned by this program and holds a counter is
+/// left untouched, so repeated calls are a safe no-op. That includes counters
+/// resized past the current layout and legacy layouts still awaiting Migrate.
+/// Otherwise this runs the normal initialize flow with a step of 1, no bounds
+/// and no saturation.
 ///
 /// Accounts expected:
-/// 1. `[writable]` Counter account to increment
-fn process_increment_counter(
+/// 1. `[signer, writable]` Counter account to create or reuse
+/// 2. `[signer, writable]` Payer account, which funds the counter
+/// 3. `[]` System Program
+/// 4. `[]` Optional counter authority, defaulting to the payer
+fn process_initialize_or_get(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    initial_value: u64,
 ) -> ProgramResult {
+    check_account_count(accounts, CREATE_ACCOUNTS)?;
     let accounts_iter = &mut accounts.iter();
     let counter_account = next_account_info(accounts_iter)?;
 
-    // Verify ownership
-    if counter_account.owner != program_id {
-        return Err(ProgramError::IncorrectProgramId);
+    // Skip the create_account CPI, which would fail on an existing account
+    let data_len = counter_account.data_len();
+    let is_legacy = data_len == CounterAccount::V1_LEN
+        || counter_account
+            .data
+            .borrow()
+            .first()
+            .and_then(|&version| CounterAccount::legacy_len(version))
+            .is_some_and(|len| data_len >= len);
+    if counter_account.owner == program_id && (data_len >= CounterAccount::LEN || is_legacy) {
+        // This account was not funded by our create_account, so check it
+        check_rent_exempt(counter_account, data_len)?;
+        msg!("Counter already initialized, leaving it untouched");
+        return Ok(());
     }
 
-    // Read, update, write
-    let mut data = counter_account.data.borrow_mut();
-    let mut counter_data: CounterAccount = CounterAccount::try_from_slice(&data)?;
-
-    counter_data.count = counter_data
-        .count
-        .checked_add(1)
-        .ok_or(ProgramError::InvalidAccountData)?;
+    process_initialize_counter(
+        program_id,
+        accounts,
+        initial_value,
+        CounterSettings::default(),
+    )
+}
 
-    counter_data.serialize(&mut &mut data[..])?;
+/// Initialize a counter and apply its first increment in one instruction
+///
+/// The payer becomes the authority and signs the increment.
+///
+/// Accounts expected:
+/// 1. `[signer, writable]` Counter account to create
+/// 2. `[signer, writable]` Payer account, which becomes the counter authority
+/// 3. `[]` System Program
+fn process_initialize_and_increment(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+) -> ProgramResult {
+    check_account_count(accounts, CREATE_ACCOUNTS)?;
 
-    msg!("Counter incremented to: {}", counter_data.count);
+    // A separate authority would be stored by initialize but could not sign
+    // the increment, which checks the payer
+    if accounts.len() > CREATE_ACCOUNTS.len() {
+        msg!(
+            "InitializeAndIncrement takes exactly {} accounts, the payer becomes the authority",
+            CREATE_ACCOUNTS.len()
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    Ok(())
+    process_initialize_counter(
+        program_id,
+        accounts,
+        initial_value,
+        CounterSettings::default(),
+    )?;
+    process_increment_counter(program_id, accounts)
 }
 
-/// Counter account data structure
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub struct CounterAccount {
-    /// Current counter value
-    pub count: u64,
-}
+/// Compute units budgeted for each counter `BatchInitialize` creates: the
+/// `create_account` CPI plus the initialize checks and logs, rounded up
+pub const BATCH_COUNTER_COMPUTE_UNITS: u64 = 10_000;
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use litesvm::LiteSVM;
-    use solana_sdk::{
-        account::ReadableAccount,
-        instruction::{AccountMeta, Instruction},
-        message::Message,
-        signature::{Keypair, Signer},
-        system_program,
-        transaction::Transaction,
-    };
+/// Most counters one `BatchInitialize` creates, keeping the batch well inside
+/// the default 200,000 compute units of an instruction
+pub const MAX_BATCH_COUNTERS: usize = 16;
 
-    #[test]
-    fn test_counter_program() {
-        // Create a new instance of the Solana VM for testing
-        let mut svm = LiteSVM::new();
+/// Initialize several counters in one instruction
+///
+/// Each counter goes through the normal initialize flow with a step of 1, no
+/// bounds and no saturation, and the payer becomes its authority.
+///
+/// Batches above `MAX_BATCH_COUNTERS`, or whose estimated cost exceeds the
+/// compute units left, are refused before any counter is created so the
+/// budget never runs out halfway through.
+///
+/// Accounts expected:
+/// 1. `[signer, writable]` Payer account, which funds every counter
+/// 2. `[]` System Program
+/// 3. `[signer, writable]` One counter account to create per value
+fn process_batch_initialize(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    values: &[u64],
+) -> ProgramResult {
+    check_account_count(accounts, BATCH_CREATE_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let counter_accounts = accounts_iter.as_slice();
 
-        // Create a keypair for the transaction payer
-        let payer = Keypair::new();
+    if values.len() > MAX_BATCH_COUNTERS {
+        msg!(
+            "Batch of {} counters is above the limit of {}",
+            values.len(),
+            MAX_BATCH_COUNTERS
+        );
+        return Err(CounterError::BatchTooLarge.into());
+    }
 
-        // Fund the payer account with 1 SOL (1 billion lamports)
-        // This is needed to pay for transaction fees and account creation
-        svm.airdrop(&payer.pubkey(), 1_000_000_000)
-            .expect("Failed to airdrop");
+    let estimated_units = values.len() as u64 * BATCH_COUNTER_COMPUTE_UNITS;
+    let remaining_units = sol_remaining_compute_units();
+    if estimated_units > remaining_units {
+        msg!(
+            "Batch of {} counters needs about {} compute units, only {} remain",
+            values.len(),
+            estimated_units,
+            remaining_units
+        );
+        return Err(CounterError::BatchTooLarge.into());
+    }
 
-            
-        // Load our compiled program into the test environment
-        let program_keypair = Keypair::new();
-        let program_id = program_keypair.pubkey();
+    if counter_accounts.len() != values.len() {
+        msg!(
+            "Expected {} counter accounts for {} values, got {}",
+            values.len(),
+            values.len(),
+            counter_accounts.len()
+        );
+        return Err(if counter_accounts.len() < values.len() {
+            ProgramError::NotEnoughAccountKeys
+        } else {
+            ProgramError::InvalidArgument
+        });
+    }
 
-        // Load the compiled program
-        svm.add_program_from_file(
+    for (counter_account, &initial_value) in counter_accounts.iter().zip(values) {
+        let create_accounts = [
+            counter_account.clone(),
+            payer_account.clone(),
+            system_program.clone(),
+        ];
+        process_initialize_counter(
             program_id,
-            "target/deploy/counter_program.so"
-        ).expect("Failed to load program");
+            &create_accounts,
+            initial_value,
+            CounterSettings::default(),
+        )?;
+    }
 
-                // Step 1: Initialize the counter
-        let counter_keypair = Keypair::new();
-        let initial_value: u64 = 42;
+    msg!("Initialized {} counters", values.len());
 
-        println!("Testing counter initialization...");
+    Ok(())
+}
 
-        // Use Borsh serialization for the instruction
-        let init_instruction_data =
-            borsh::to_vec(&CounterInstruction::InitializeCounter { initial_value })
-                .expect("Failed to serialize instruction");
+/// Initialize a new counter at a program derived address
+///
+/// The address is derived from `[COUNTER_SEED, authority]`, so each
+/// authority gets exactly one deterministic counter, or from
+/// `[COUNTER_SEED, authority, name]` for one counter per name.
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter PDA to create
+/// 2. `[signer, writable]` Payer account, which becomes the counter authority
+/// 3. `[]` System Program
+fn process_initialize_pda_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+    name: Option<[u8; NAME_LEN]>,
+) -> ProgramResult {
+    check_account_count(
+        accounts,
+        &[
+            "counter PDA (writable)",
+            "payer (signer, writable)",
+            "system program",
+        ],
+    )?;
+    let accounts_iter = &mut accounts.iter();
 
-        // Create the initialization instruction
-        let initialize_instruction = Instruction::new_with_bytes(
-            program_id,
-            &init_instruction_data,
-            vec![
-                // Account 1: Counter account (signer, writable)
-                AccountMeta::new(counter_keypair.pubkey(), true),
-                // Account 2: Payer (signer, writable)
-                AccountMeta::new(payer.pubkey(), true),
-                // Account 3: System Program (not signer, not writable)
-                AccountMeta::new_readonly(system_program::id(), false),
-            ],
-        );
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
 
-        // Build and send the transaction
-        let message = Message::new(&[initialize_instruction], Some(&payer.pubkey()));
-        let transaction = Transaction::new(
-            &[&payer, &counter_keypair],  // Signers
-            message,
-            svm.latest_blockhash()
+    // create_account would fail on an existing account, so say why up front
+    if counter_account.owner == program_id || !counter_account.data_is_empty() {
+        msg!(
+            "Counter account {} is already initialized",
+            counter_account.key
         );
+        return Err(CounterError::AlreadyInitialized.into());
+    }
 
-        let result = svm.send_transaction(transaction);
-        assert!(result.is_ok(), "Initialize transaction should succeed");
+    // Verify the passed address is the PDA for this authority
+    let (expected_address, bump) = match &name {
+        Some(name) => find_named_counter_address(payer_account.key, name, program_id),
+        None => find_counter_address(payer_account.key, program_id),
+    };
+    if *counter_account.key != expected_address {
+        msg!("Counter account does not match the derived PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
 
-        let logs = result.unwrap().logs;
+    let account_space = CounterAccount::LEN;
+    let rent = Rent::get()?;
+    let required_lamports = rent.minimum_balance(account_space);
+
+    let bump_seed = [bump];
+    let mut seeds: Vec<&[u8]> = vec![COUNTER_SEED, payer_account.key.as_ref()];
+    if let Some(name) = &name {
+        seeds.push(name);
+    }
+    seeds.push(&bump_seed);
+
+    // Create account via CPI to System Program, signing for the PDA
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account.key,
+            counter_account.key,
+            required_lamports,
+            account_space as u64,
+            program_id,
+        ),
+        &[
+            payer_account.clone(),
+            counter_account.clone(),
+            system_program.clone(),
+        ],
+        &[&seeds],
+    )?;
+
+    // Initialize counter data
+    let counter_data = CounterAccount {
+        bump,
+        name: name.unwrap_or_default(),
+        ..CounterAccount::new(initial_value, *payer_account.key)?
+    };
+
+    let mut account_data = &mut counter_account.data.borrow_mut()[..];
+    counter_data.serialize(&mut account_data)?;
+
+    msg!(
+        "PDA counter {} initialized with value: {} (update {} at {})",
+        counter_account.key,
+        initial_value,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Initialized {
+        value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Increment an existing counter by its step
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to increment
+/// 2. `[signer]` Counter authority
+fn process_increment_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+    counter_data.start_cooldown()?;
+
+    // Stay within the configured maximum, clamping if the counter saturates
+    let next = i128::from(counter_data.count) + i128::from(counter_data.step);
+    counter_data.count = counter_data.bound(next)?;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter incremented to: {} (update {} at {})",
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Incremented {
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    // Hand the new value straight back to the caller
+    set_return_data(&counter_data.count.to_le_bytes());
+
+    Ok(())
+}
+
+/// Increment an existing counter several times at once
+///
+/// Applies `times` increments of 1 in a single checked addition rather than
+/// looping, and stops at the counter maximum like a single increment. The
+/// batch counts as one increment for the cooldown.
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to increment
+/// 2. `[signer]` Counter authority
+fn process_increment_by(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    times: u32,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    if times == 0 {
+        msg!("IncrementBy needs at least one increment");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+    counter_data.check_max_step(times as u64)?;
+    counter_data.start_cooldown()?;
+
+    let next = i128::from(counter_data.count) + i128::from(times);
+    counter_data.count = counter_data.bound(next)?;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter incremented {} times to: {} (update {} at {})",
+        times,
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Incremented {
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Decrement an existing counter
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to decrement
+/// 2. `[signer]` Counter authority
+fn process_decrement_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+
+    // Never let the counter drop below its minimum (0 by default), clamping
+    // if the counter saturates
+    counter_data.count = counter_data.bound(i128::from(counter_data.count) - 1)?;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter decremented to: {} (update {} at {})",
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Decremented {
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Overwrite the value of an existing counter
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Counter authority
+fn process_set_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    value: u64,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+
+    counter_data.check_bounds(value)?;
+    if value > counter_data.count {
+        counter_data.start_cooldown()?;
+    }
+
+    let old_value = counter_data.count;
+    counter_data.count = value;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter set from {} to {} (update {} at {})",
+        old_value,
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Set {
+        old_value,
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Reset an existing counter to 0
+///
+/// Succeeds without changes if the counter is already 0.
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to reset
+/// 2. `[signer]` Counter authority
+fn process_reset_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+
+    // A counter with a minimum above 0 can never be reset
+    counter_data.check_bounds(0)?;
+
+    let old_value = counter_data.count;
+    counter_data.count = 0;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter reset from {} to 0 (update {} at {})",
+        old_value,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Reset { old_value }.emit()?;
+
+    Ok(())
+}
+
+/// Add an arbitrary amount to an existing counter
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Counter authority
+fn process_add_to_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+    counter_data.check_max_step(amount)?;
+    counter_data.start_cooldown()?;
+
+    let next = i128::from(counter_data.count) + i128::from(amount);
+    counter_data.count = counter_data.bound(next)?;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter increased by {} to: {} (update {} at {})",
+        amount,
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Added {
+        amount,
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Add an amount to an existing counter, clamping at its maximum and
+/// reporting how much was lost
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Counter authority
+fn process_add_with_overflow_return(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+    counter_data.check_max_step(amount)?;
+    counter_data.start_cooldown()?;
+
+    // Whatever doesn't fit below the maximum is handed back to the caller
+    let (count, remainder) = clamped_add(counter_data.count, amount, counter_data.max);
+    let added = amount - remainder;
+    counter_data.count = count;
+    if remainder > 0 {
+        msg!(
+            "Counter clamped at its maximum of {}, {} of {} not added",
+            counter_data.max,
+            remainder,
+            amount
+        );
+    }
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    set_return_data(&remainder.to_le_bytes());
+
+    msg!(
+        "Counter increased by {} to: {} (update {} at {})",
+        added,
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Added {
+        amount: added,
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Subtract an amount from an existing counter, clamping at its minimum and
+/// reporting the deficit
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Counter authority
+fn process_subtract_with_deficit_return(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+    counter_data.check_max_step(amount)?;
+
+    // Whatever can't be taken above the minimum is handed back to the caller
+    let (count, deficit) = clamped_sub(counter_data.count, amount, counter_data.min);
+    let subtracted = amount - deficit;
+    counter_data.count = count;
+    if deficit > 0 {
+        msg!(
+            "Counter clamped at its minimum of {}, {} of {} not subtracted",
+            counter_data.min,
+            deficit,
+            amount
+        );
+    }
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    set_return_data(&deficit.to_le_bytes());
+
+    msg!(
+        "Counter decreased by {} to: {} (update {} at {})",
+        subtracted,
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Subtracted {
+        amount: subtracted,
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Overwrite the value of an existing counter with the return data of a CPI
+///
+/// The source program is invoked with no accounts, so it can only compute
+/// from `data`, e.g. the byte calculator.
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Counter authority
+/// 3. `[]` Source program to invoke
+fn process_set_from_return_data(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    source: Pubkey,
+    data: Vec<u8>,
+) -> ProgramResult {
+    check_account_count(
+        accounts,
+        &["counter (writable)", "authority (signer)", "source program"],
+    )?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let source_program = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    if *source_program.key != source {
+        msg!(
+            "Expected source program {}, got {}",
+            source,
+            source_program.key
+        );
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // Check the caller may change the counter before running the CPI
+    {
+        let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+        check_authority(program_id, &counter_data, authority_account, accounts)?;
+        counter_data.check_not_paused()?;
+    }
+
+    invoke(
+        &Instruction::new_with_bytes(source, &data, Vec::new()),
+        std::slice::from_ref(source_program),
+    )?;
+
+    // A program the source called in turn could have set the data instead
+    let value = match get_return_data() {
+        Some((returned_by, bytes)) if returned_by == source => {
+            <[u8; 8]>::try_from(bytes.as_slice()).map_err(|_| {
+                msg!("Expected 8 bytes of return data, got {}", bytes.len());
+                ProgramError::InvalidArgument
+            })?
+        }
+        Some((returned_by, _)) => {
+            msg!("Return data was set by {}, not {}", returned_by, source);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        None => {
+            msg!("Source program {} returned no data", source);
+            return Err(ProgramError::InvalidArgument);
+        }
+    };
+
+    // Read, update, write
+    let mut account_data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&account_data)?;
+
+    // Another program chose this value, so hold it to the bounds
+    let value = u64::from_le_bytes(value);
+    counter_data.check_bounds(value)?;
+    if value > counter_data.count {
+        counter_data.start_cooldown()?;
+    }
+
+    let old_value = counter_data.count;
+    counter_data.count = value;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut account_data[..])?;
+
+    msg!(
+        "Counter set from {} to {} returned by {} (update {} at {})",
+        old_value,
+        counter_data.count,
+        source,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Set {
+        old_value,
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Copy a counter into a snapshot account, creating the snapshot on first use
+///
+/// The counter itself is left untouched, so paused counters can still be
+/// checkpointed.
+///
+/// Accounts expected:
+/// 1. `[]` Counter account to copy
+/// 2. `[writable]` Snapshot account, also `[signer]` when it is created
+/// 3. `[signer, writable]` Counter authority, who pays for a new snapshot
+/// 4. `[]` System Program
+fn process_snapshot(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(
+        accounts,
+        &[
+            "counter",
+            "snapshot (writable)",
+            "authority (signer, writable)",
+            "system program",
+        ],
+    )?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let snapshot_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(snapshot_account)?;
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+
+    if snapshot_account.data_is_empty() {
+        let account_space = CounterSnapshot::LEN;
+        let rent = Rent::get()?;
+        let required_lamports = rent.minimum_balance(account_space);
+
+        // Create account via CPI to System Program
+        invoke(
+            &system_instruction::create_account(
+                authority_account.key,
+                snapshot_account.key,
+                required_lamports,
+                account_space as u64,
+                program_id,
+            ),
+            &[
+                authority_account.clone(),
+                snapshot_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    } else {
+        if snapshot_account.owner != program_id {
+            msg!("Snapshot account must be owned by the counter program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // Only overwrite a snapshot taken under the same authority
+        let previous = CounterSnapshot::try_from_slice(&snapshot_account.data.borrow())
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if previous.state.authority != counter_data.authority {
+            msg!(
+                "Snapshot {} belongs to authority {}",
+                snapshot_account.key,
+                previous.state.authority
+            );
+            return Err(CounterError::Unauthorized.into());
+        }
+    }
+
+    let snapshot = CounterSnapshot {
+        counter: *counter_account.key,
+        taken_at: Clock::get()?.unix_timestamp,
+        state: counter_data,
+    };
+    snapshot.serialize(&mut &mut snapshot_account.data.borrow_mut()[..])?;
+
+    msg!(
+        "Counter {} snapshotted at value {} into {} (at {})",
+        counter_account.key,
+        snapshot.state.count,
+        snapshot_account.key,
+        snapshot.taken_at
+    );
+
+    Ok(())
+}
+
+/// Revert a counter to the value held in a snapshot account
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to restore
+/// 2. `[]` Snapshot account to read
+/// 3. `[signer]` Counter authority
+fn process_restore(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(
+        accounts,
+        &["counter (writable)", "snapshot", "authority (signer)"],
+    )?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let snapshot_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id || snapshot_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    let snapshot = CounterSnapshot::try_from_slice(&snapshot_account.data.borrow())
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+
+    // Read, update, write
+    let mut account_data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&account_data)?;
+
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    if snapshot.state.authority != counter_data.authority {
+        msg!(
+            "Snapshot was taken under authority {}, not {}",
+            snapshot.state.authority,
+            counter_data.authority
+        );
+        return Err(CounterError::Unauthorized.into());
+    }
+    counter_data.check_not_paused()?;
+
+    // The snapshot may come from another counter with wider bounds
+    counter_data.check_bounds(snapshot.state.count)?;
+    if snapshot.state.count > counter_data.count {
+        counter_data.start_cooldown()?;
+    }
+
+    let old_value = counter_data.count;
+    counter_data.count = snapshot.state.count;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut account_data[..])?;
+
+    msg!(
+        "Counter restored from {} to {} taken at {} (update {} at {})",
+        old_value,
+        counter_data.count,
+        snapshot.taken_at,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Set {
+        old_value,
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Set an existing counter to a new value if it still holds the value the
+/// caller last read
+///
+/// Nothing is written on a mismatch, so concurrent read-modify-write
+/// clients can't silently overwrite each other's updates.
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Counter authority
+fn process_compare_and_set(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    expected: u64,
+    new: u64,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+
+    // Hand back the value that won so the caller can retry without a read
+    if counter_data.count != expected {
+        msg!(
+            "Counter holds {}, not the expected {}",
+            counter_data.count,
+            expected
+        );
+        set_return_data(&counter_data.count.to_le_bytes());
+        return Err(CounterError::CasFailed.into());
+    }
+    counter_data.check_bounds(new)?;
+    if new > counter_data.count {
+        counter_data.start_cooldown()?;
+    }
+
+    let old_value = counter_data.count;
+    counter_data.count = new;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter swapped from {} to {} (update {} at {})",
+        old_value,
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Set {
+        old_value,
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Multiply an existing counter by a factor
+///
+/// Products above the counter maximum fail with `Overflow`, and products
+/// below its minimum (only possible with a factor of 0) with `Underflow`,
+/// unless the counter saturates.
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Counter authority
+fn process_multiply_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    factor: u64,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+
+    // The product of two u64s can pass even an i128, which is above any max
+    let old_value = counter_data.count;
+    let product = i128::from(old_value)
+        .checked_mul(i128::from(factor))
+        .unwrap_or(i128::MAX);
+    // A factor of 0 or 1 never raises the count, so only a real gain is
+    // held to the step limit and the cooldown
+    if product > i128::from(old_value) {
+        let gain = u64::try_from(product - i128::from(old_value)).unwrap_or(u64::MAX);
+        counter_data.check_max_step(gain)?;
+        counter_data.start_cooldown()?;
+    }
+    counter_data.count = counter_data.bound(product)?;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter multiplied by {} from {} to: {} (update {} at {})",
+        factor,
+        old_value,
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Multiplied {
+        factor,
+        old_value,
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Subtract an arbitrary amount from an existing counter
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Counter authority
+fn process_subtract_from_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+    counter_data.check_not_paused()?;
+    counter_data.check_max_step(amount)?;
+
+    let next = i128::from(counter_data.count) - i128::from(amount);
+    counter_data.count = counter_data.bound(next)?;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter decreased by {} to: {} (update {} at {})",
+        amount,
+        counter_data.count,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::Subtracted {
+        amount,
+        new_value: counter_data.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Pause or unpause an existing counter
+///
+/// Setting the flag it already has succeeds without changes.
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Counter authority
+fn process_set_paused(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    paused: bool,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+
+    let state = if paused { "paused" } else { "unpaused" };
+    if counter_data.is_paused == paused {
+        msg!("Counter is already {}", state);
+        return Ok(());
+    }
+    counter_data.is_paused = paused;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter {} (update {} at {})",
+        state,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::PauseChanged { paused }.emit()?;
+
+    Ok(())
+}
+
+/// Subtract an amount from one counter and add it to another
+///
+/// Both counters are checked before either is written, so the value moves
+/// entirely or not at all. A transfer that would take the source below its
+/// minimum or the destination above its maximum fails even on saturating
+/// counters, since clamping one side would create or destroy value.
+///
+/// Accounts expected:
+/// 1. `[writable]` Source counter account
+/// 2. `[writable]` Destination counter account
+/// 3. `[signer]` Authority of both counters
+fn process_transfer_value(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    check_account_count(accounts, TRANSFER_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let source_account = next_account_info(accounts_iter)?;
+    let destination_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    for counter_account in [source_account, destination_account] {
+        if counter_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        check_writable(counter_account)?;
+    }
+
+    // Both sides are borrowed at once, so they must be different accounts
+    if source_account.key == destination_account.key {
+        msg!("Source and destination must be different counters");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Read, update, write
+    let mut source_bytes = source_account.data.borrow_mut();
+    let mut destination_bytes = destination_account.data.borrow_mut();
+    let mut source = CounterAccount::unpack(&source_bytes)?;
+    let mut destination = CounterAccount::unpack(&destination_bytes)?;
+    for counter_data in [&source, &destination] {
+        check_authority(program_id, counter_data, authority_account, accounts)?;
+        counter_data.check_not_paused()?;
+    }
+    destination.check_max_step(amount)?;
+    destination.start_cooldown()?;
+
+    let source_next = i128::from(source.count) - i128::from(amount);
+    if i128::from(source.bound(source_next)?) != source_next {
+        msg!(
+            "Source counter holds {}, cannot move {}",
+            source.count,
+            amount
+        );
+        return Err(CounterError::Underflow.into());
+    }
+    let destination_next = i128::from(destination.count) + i128::from(amount);
+    if i128::from(destination.bound(destination_next)?) != destination_next {
+        msg!(
+            "Destination counter holds {}, cannot take {}",
+            destination.count,
+            amount
+        );
+        return Err(CounterError::Overflow.into());
+    }
+    source.count = source_next as u64;
+    destination.count = destination_next as u64;
+
+    source.record_update()?;
+    destination.record_update()?;
+
+    source.serialize(&mut &mut source_bytes[..])?;
+    destination.serialize(&mut &mut destination_bytes[..])?;
+
+    msg!(
+        "Moved {} from {} (now {}) to {} (now {})",
+        amount,
+        source_account.key,
+        source.count,
+        destination_account.key,
+        destination.count
+    );
+
+    CounterEvent::Subtracted {
+        amount,
+        new_value: source.count,
+    }
+    .emit()?;
+    CounterEvent::Added {
+        amount,
+        new_value: destination.count,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Transfer control of a counter to a new authority
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to update
+/// 2. `[signer]` Current counter authority
+fn process_transfer_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_authority: Pubkey,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // The all-zero key is the System Program, which can never sign for the
+    // counter, so handing authority to it would brick the account
+    if new_authority == Pubkey::default() {
+        msg!("New authority cannot be the all-zero pubkey");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::unpack(&data)?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+
+    let old_authority = counter_data.authority;
+    counter_data.authority = new_authority;
+
+    counter_data.record_update()?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Counter authority transferred from {} to {} (update {} at {})",
+        old_authority,
+        counter_data.authority,
+        counter_data.updates,
+        counter_data.last_updated
+    );
+
+    CounterEvent::AuthorityTransferred {
+        old_authority,
+        new_authority: counter_data.authority,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Close a counter, sending its lamports to a destination account
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to close
+/// 2. `[signer]` Counter authority
+/// 3. `[writable]` Destination account for the reclaimed lamports
+fn process_close_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_account_count(
+        accounts,
+        &[
+            "counter (writable)",
+            "authority (signer)",
+            "destination (writable)",
+        ],
+    )?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let destination_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+
+    // Move every lamport out of the counter so the runtime garbage-collects it
+    let reclaimed_lamports = counter_account.lamports();
+    **destination_account.lamports.borrow_mut() = destination_account
+        .lamports()
+        .checked_add(reclaimed_lamports)
+        .ok_or(CounterError::Overflow)?;
+    **counter_account.lamports.borrow_mut() = 0;
+
+    // Wipe the data and hand the account back to the System Program
+    counter_account.data.borrow_mut().fill(0);
+    counter_account.assign(&system_program::id());
+    counter_account.realloc(0, false)?;
+
+    msg!(
+        "Counter closed, {} lamports sent to {}",
+        reclaimed_lamports,
+        destination_account.key
+    );
+
+    CounterEvent::Closed {
+        lamports: reclaimed_lamports,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Return the current value of a counter without mutating it
+///
+/// The count is set as little-endian `u64` return data. The data is only
+/// borrowed immutably, so a counter passed writable as part of a larger
+/// transaction is fine, but a writable account with no data is refused as a
+/// likely mix-up with an account the caller meant to create.
+///
+/// Accounts expected:
+/// 1. `[]` Counter account to read
+fn process_get_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_account_count(accounts, READ_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+
+    if counter_account.is_writable && counter_account.data_is_empty() {
+        msg!(
+            "GetCounter got writable account {} with no data",
+            counter_account.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+
+    set_return_data(&counter_data.count.to_le_bytes());
+
+    msg!("Counter value: {}", counter_data.count);
+
+    Ok(())
+}
+
+/// Return the values of several counters in one call without mutating them
+///
+/// Accounts expected:
+/// 1. `[]` Counter accounts to read, at least one
+fn process_get_counters(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(accounts, READ_ACCOUNTS)?;
+
+    // The count prefix and every value must fit in the return data
+    let max_counters = (MAX_RETURN_DATA - 4) / 8;
+    if accounts.len() > max_counters {
+        msg!(
+            "Can read at most {} counters at once, got {}",
+            max_counters,
+            accounts.len()
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut values = Vec::with_capacity(accounts.len());
+    for counter_account in accounts {
+        // Verify ownership
+        if counter_account.owner != program_id {
+            msg!(
+                "Counter {} is not owned by the counter program",
+                counter_account.key
+            );
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        match CounterAccount::unpack(&counter_account.data.borrow()) {
+            Ok(counter_data) => values.push(counter_data.count),
+            Err(err) if err == CounterError::NotInitialized.into() => {
+                msg!("Skipping uninitialized counter {}", counter_account.key);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(4 + 8 * values.len());
+    bytes.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for value in &values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    set_return_data(&bytes);
+
+    msg!("Counter values: {:?}", values);
+
+    Ok(())
+}
+
+/// Return whether a counter's value is proven to be in a Merkle tree without
+/// mutating it
+///
+/// Accounts expected:
+/// 1. `[]` Counter account to read
+fn process_verify_count_proof(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &[[u8; 32]],
+    root: &[u8; 32],
+) -> ProgramResult {
+    check_account_count(accounts, READ_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+
+    let computed_root = proof
+        .iter()
+        .fold(count_leaf(counter_data.count), |node, sibling| {
+            merkle_parent(&node, sibling)
+        });
+    let verified = computed_root == *root;
+
+    set_return_data(&[verified as u8]);
+
+    msg!(
+        "Proof of count {} with {} siblings is {}",
+        counter_data.count,
+        proof.len(),
+        if verified { "valid" } else { "invalid" }
+    );
+
+    Ok(())
+}
+
+/// Return whether a counter holds an odd value without mutating it
+///
+/// Accounts expected:
+/// 1. `[]` Counter account to read
+fn process_get_parity(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(accounts, READ_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+    let parity = (counter_data.count % 2) as u8;
+
+    set_return_data(&[parity]);
+
+    msg!(
+        "Counter value {} is {}",
+        counter_data.count,
+        if parity == 0 { "even" } else { "odd" }
+    );
+
+    Ok(())
+}
+
+/// Return the highest and lowest values a counter has held without
+/// mutating it
+///
+/// Accounts expected:
+/// 1. `[]` Counter account to read
+fn process_get_extremes(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(accounts, READ_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&counter_data.high.to_le_bytes());
+    bytes[8..].copy_from_slice(&counter_data.low.to_le_bytes());
+    set_return_data(&bytes);
+
+    msg!(
+        "Counter high: {}, low: {}",
+        counter_data.high,
+        counter_data.low
+    );
+
+    Ok(())
+}
+
+/// Return a counter's name without mutating it
+///
+/// Accounts expected:
+/// 1. `[]` Counter account to read
+fn process_get_name(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(accounts, READ_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+
+    set_return_data(counter_data.label());
+
+    msg!(
+        "Counter name: {:?}",
+        std::str::from_utf8(counter_data.label()).unwrap_or_default()
+    );
+
+    Ok(())
+}
+
+/// Return the PDA bump stored in a counter account
+///
+/// Layouts older than the bump field are refused by `CounterAccount::unpack`
+/// and need a `Migrate` first.
+///
+/// Accounts expected:
+/// 1. `[]` Counter account to read
+fn process_get_bump(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(accounts, READ_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+
+    set_return_data(&[counter_data.bump]);
+
+    msg!("Counter bump: {}", counter_data.bump);
+
+    Ok(())
+}
+
+/// Return the counter's recent values as return data
+///
+/// Accounts expected:
+/// 1. `[]` Counter account to read
+fn process_get_history(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(accounts, READ_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+
+    let values = counter_data.recent_values();
+    let bytes: Vec<u8> = values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    set_return_data(&bytes);
+
+    msg!("Counter history: {:?}", values);
+
+    Ok(())
+}
+
+/// Initialize a new wide (`u128`) counter account
+///
+/// Wide counters use their own `WideCounterAccount` layout so existing `u64`
+/// counters keep working unchanged.
+///
+/// Accounts expected:
+/// 1. `[signer, writable]` Counter account to create
+/// 2. `[signer, writable]` Payer account, which becomes the counter authority
+/// 3. `[]` System Program
+fn process_initialize_wide_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u128,
+) -> ProgramResult {
+    check_account_count(accounts, CREATE_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
+
+    if counter_account.owner == program_id || !counter_account.data_is_empty() {
+        msg!(
+            "Counter account {} is already initialized",
+            counter_account.key
+        );
+        return Err(CounterError::AlreadyInitialized.into());
+    }
+
+    let account_space = WideCounterAccount::LEN;
+    let rent = Rent::get()?;
+    let required_lamports = rent.minimum_balance(account_space);
+
+    // Create account via CPI to System Program
+    invoke(
+        &system_instruction::create_account(
+            payer_account.key,
+            counter_account.key,
+            required_lamports,
+            account_space as u64,
+            program_id,
+        ),
+        &[
+            payer_account.clone(),
+            counter_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+
+    let counter_data = WideCounterAccount {
+        count: initial_value,
+        authority: *payer_account.key,
+    };
+
+    let mut account_data = &mut counter_account.data.borrow_mut()[..];
+    counter_data.serialize(&mut account_data)?;
+
+    msg!("Wide counter initialized with value: {}", initial_value);
+
+    Ok(())
+}
+
+/// Increment an existing wide counter by 1
+///
+/// Accounts expected:
+/// 1. `[writable]` Wide counter account to increment
+/// 2. `[signer]` Counter authority
+fn process_increment_wide_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data =
+        WideCounterAccount::try_from_slice(&data).map_err(|_| CounterError::NotInitialized)?;
+
+    if !authority_account.is_signer || *authority_account.key != counter_data.authority {
+        msg!("Counter authority must sign");
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    counter_data.count = counter_data
+        .count
+        .checked_add(1)
+        .ok_or(CounterError::Overflow)?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!("Wide counter incremented to: {}", counter_data.count);
+
+    Ok(())
+}
+
+/// Initialize a new signed (`i64`) counter account
+///
+/// Signed counters use their own `SignedCounterAccount` layout so existing
+/// unsigned counters keep working unchanged.
+///
+/// Accounts expected:
+/// 1. `[signer, writable]` Counter account to create
+/// 2. `[signer, writable]` Payer account, which becomes the counter authority
+/// 3. `[]` System Program
+fn process_initialize_signed_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: i64,
+) -> ProgramResult {
+    check_account_count(accounts, CREATE_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
+
+    if counter_account.owner == program_id || !counter_account.data_is_empty() {
+        msg!(
+            "Counter account {} is already initialized",
+            counter_account.key
+        );
+        return Err(CounterError::AlreadyInitialized.into());
+    }
+
+    let account_space = SignedCounterAccount::LEN;
+    let rent = Rent::get()?;
+    let required_lamports = rent.minimum_balance(account_space);
+
+    // Create account via CPI to System Program
+    invoke(
+        &system_instruction::create_account(
+            payer_account.key,
+            counter_account.key,
+            required_lamports,
+            account_space as u64,
+            program_id,
+        ),
+        &[
+            payer_account.clone(),
+            counter_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+
+    let counter_data = SignedCounterAccount {
+        count: initial_value,
+        authority: *payer_account.key,
+    };
+
+    let mut account_data = &mut counter_account.data.borrow_mut()[..];
+    counter_data.serialize(&mut account_data)?;
+
+    msg!("Signed counter initialized with value: {}", initial_value);
+
+    Ok(())
+}
+
+/// Add `delta` to an existing signed counter
+///
+/// Accounts expected:
+/// 1. `[writable]` Signed counter account to update
+/// 2. `[signer]` Counter authority
+fn process_update_signed_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delta: i64,
+) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data =
+        SignedCounterAccount::try_from_slice(&data).map_err(|_| CounterError::NotInitialized)?;
+
+    if !authority_account.is_signer || *authority_account.key != counter_data.authority {
+        msg!("Counter authority must sign");
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    let out_of_range = if delta > 0 {
+        CounterError::Overflow
+    } else {
+        CounterError::Underflow
+    };
+    counter_data.count = counter_data.count.checked_add(delta).ok_or(out_of_range)?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!("Signed counter updated to: {}", counter_data.count);
+
+    Ok(())
+}
+
+/// Initialize a new mean counter account with no samples
+///
+/// Mean counters use their own `MeanCounterAccount` layout so existing
+/// counters keep working unchanged.
+///
+/// Accounts expected:
+/// 1. `[signer, writable]` Counter account to create
+/// 2. `[signer, writable]` Payer account, which becomes the counter authority
+/// 3. `[]` System Program
+fn process_initialize_mean_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(accounts, CREATE_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
+
+    if counter_account.owner == program_id || !counter_account.data_is_empty() {
+        msg!(
+            "Counter account {} is already initialized",
+            counter_account.key
+        );
+        return Err(CounterError::AlreadyInitialized.into());
+    }
+
+    let account_space = MeanCounterAccount::LEN;
+    let rent = Rent::get()?;
+    let required_lamports = rent.minimum_balance(account_space);
+
+    // Create account via CPI to System Program
+    invoke(
+        &system_instruction::create_account(
+            payer_account.key,
+            counter_account.key,
+            required_lamports,
+            account_space as u64,
+            program_id,
+        ),
+        &[
+            payer_account.clone(),
+            counter_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+
+    let counter_data = MeanCounterAccount {
+        sum: 0,
+        samples: 0,
+        authority: *payer_account.key,
+    };
+
+    let mut account_data = &mut counter_account.data.borrow_mut()[..];
+    counter_data.serialize(&mut account_data)?;
+
+    msg!("Mean counter initialized");
+
+    Ok(())
+}
+
+/// Add a sample to an existing mean counter
+///
+/// Accounts expected:
+/// 1. `[writable]` Mean counter account to update
+/// 2. `[signer]` Counter authority
+fn process_add_sample(program_id: &Pubkey, accounts: &[AccountInfo], value: u64) -> ProgramResult {
+    check_account_count(accounts, AUTHORIZED_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    // Read, update, write
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data =
+        MeanCounterAccount::try_from_slice(&data).map_err(|_| CounterError::NotInitialized)?;
+
+    if !authority_account.is_signer || *authority_account.key != counter_data.authority {
+        msg!("Counter authority must sign");
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    counter_data.sum = counter_data
+        .sum
+        .checked_add(u128::from(value))
+        .ok_or(CounterError::Overflow)?;
+    counter_data.samples = counter_data
+        .samples
+        .checked_add(1)
+        .ok_or(CounterError::Overflow)?;
+
+    counter_data.serialize(&mut &mut data[..])?;
+
+    msg!(
+        "Sample {} added: sum {} over {} samples",
+        value,
+        counter_data.sum,
+        counter_data.samples
+    );
+
+    Ok(())
+}
+
+/// Return the mean of a mean counter's samples without mutating it
+///
+/// The mean is rounded down and set as little-endian `u64` return data. It
+/// never exceeds the largest sample, so it always fits.
+///
+/// Accounts expected:
+/// 1. `[]` Mean counter account to read
+fn process_get_mean(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    check_account_count(accounts, READ_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let counter_data = MeanCounterAccount::try_from_slice(&counter_account.data.borrow())
+        .map_err(|_| CounterError::NotInitialized)?;
+    if counter_data.samples == 0 {
+        msg!("Mean counter has no samples yet");
+        return Err(CounterError::NoSamples.into());
+    }
+
+    let mean = counter_data.sum / u128::from(counter_data.samples);
+    let mean = u64::try_from(mean).map_err(|_| CounterError::Overflow)?;
+    set_return_data(&mean.to_le_bytes());
+
+    msg!("Mean of {} samples: {}", counter_data.samples, mean);
+
+    Ok(())
+}
+
+/// Upgrade a counter to the current layout version
+///
+/// Version 1 counters only stored `count` and no authority, so the counter
+/// account itself must sign, proving the caller holds the keypair it was
+/// created with. The migrating authority then takes over the counter and
+/// every other field takes its default. Later versions only append fields,
+/// so their stored prefix is kept, the new fields take their defaults and
+/// the stored authority must sign. The account grows to the new size,
+/// keeping any spare room from `Resize`, and is topped up to stay
+/// rent-exempt. Counters already at the current version are left untouched,
+/// and newer versions are refused rather than downgraded.
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to migrate, also `[signer]` at version 1
+/// 2. `[signer, writable]` Authority, which also pays any extra rent
+/// 3. `[]` System Program
+fn process_migrate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_account_count(accounts, RESIZE_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    let counter_data = if counter_account.data_len() == CounterAccount::V1_LEN {
+        // Without a stored authority anyone could otherwise claim the counter
+        if !counter_account.is_signer {
+            msg!(
+                "Version 1 counter {} must sign its own migration",
+                counter_account.key
+            );
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let data = counter_account.data.borrow();
+        let count = u64::try_from_slice(&data)?;
+        CounterAccount::new(count, *authority_account.key)?
+    } else {
+        let version = *counter_account
+            .data
+            .borrow()
+            .first()
+            .ok_or(CounterError::NotInitialized)?;
+        if version == CounterAccount::VERSION {
+            msg!("Counter is already at version {}", version);
+            return Ok(());
+        }
+
+        let legacy_len =
+            CounterAccount::legacy_len(version).filter(|len| *len <= counter_account.data_len());
+        let Some(legacy_len) = legacy_len else {
+            msg!("Cannot migrate counter from version {}", version);
+            return Err(CounterError::UnsupportedVersion.into());
+        };
+
+        let mut bytes = borsh::to_vec(&CounterAccount::new(0, Pubkey::default())?)?;
+        bytes[..legacy_len].copy_from_slice(&counter_account.data.borrow()[..legacy_len]);
+        bytes[0] = CounterAccount::VERSION;
+        let mut counter_data = CounterAccount::try_from_slice(&bytes)?;
+        check_authority(program_id, &counter_data, authority_account, accounts)?;
+
+        // Layouts before version 7 never tracked extremes, so start from the
+        // current value
+        if version < 7 {
+            counter_data.high = counter_data.count;
+            counter_data.low = counter_data.count;
+        }
+        counter_data
+    };
+
+    if !authority_account.is_signer {
+        msg!("Migrating authority must sign");
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    // Keep any spare room reserved with Resize
+    let new_len = CounterAccount::LEN.max(counter_account.data_len());
+    resize_with_rent(counter_account, authority_account, system_program, new_len)?;
+
+    counter_data.serialize(&mut &mut counter_account.data.borrow_mut()[..])?;
+
+    msg!(
+        "Counter migrated to version {} with value {}",
+        counter_data.version,
+        counter_data.count
+    );
+
+    Ok(())
+}
+
+/// Reallocate a counter account, for example to reserve room ahead of a
+/// layout upgrade
+///
+/// Accounts expected:
+/// 1. `[writable]` Counter account to resize
+/// 2. `[signer, writable]` Counter authority, which pays any extra rent
+/// 3. `[]` System Program
+fn process_resize(program_id: &Pubkey, accounts: &[AccountInfo], new_len: u64) -> ProgramResult {
+    check_account_count(accounts, RESIZE_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow())?;
+    check_authority(program_id, &counter_data, authority_account, accounts)?;
+
+    let new_len = usize::try_from(new_len).map_err(|_| ProgramError::InvalidArgument)?;
+    if new_len < CounterAccount::LEN {
+        msg!(
+            "Counter account cannot shrink below {} bytes",
+            CounterAccount::LEN
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    resize_with_rent(counter_account, authority_account, system_program, new_len)?;
+
+    msg!("Counter account resized to {} bytes", new_len);
+
+    Ok(())
+}
+
+/// Initialize a multisig account that can act as a counter authority
+///
+/// Accounts expected:
+/// 1. `[signer, writable]` Multisig account to create
+/// 2. `[signer, writable]` Payer account
+/// 3. `[]` System Program
+fn process_initialize_multisig(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    signers: Vec<Pubkey>,
+    threshold: u8,
+) -> ProgramResult {
+    check_account_count(accounts, CREATE_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+
+    let multisig_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
+
+    if multisig_account.owner == program_id || !multisig_account.data_is_empty() {
+        msg!(
+            "Multisig account {} is already initialized",
+            multisig_account.key
+        );
+        return Err(CounterError::AlreadyInitialized.into());
+    }
+
+    if signers.len() > MultisigAccount::MAX_SIGNERS {
+        msg!(
+            "Multisig allows at most {} signers, got {}",
+            MultisigAccount::MAX_SIGNERS,
+            signers.len()
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if threshold == 0 || usize::from(threshold) > signers.len() {
+        msg!(
+            "Multisig threshold {} must be between 1 and {}",
+            threshold,
+            signers.len()
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // A repeated signer would count twice towards the threshold
+    if signers
+        .iter()
+        .enumerate()
+        .any(|(index, signer)| signers[..index].contains(signer))
+    {
+        msg!("Multisig signers must be distinct");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let multisig_data = MultisigAccount { threshold, signers };
+    let account_space = multisig_data.space();
+    let required_lamports = Rent::get()?.minimum_balance(account_space);
+
+    // Create account via CPI to System Program
+    invoke(
+        &system_instruction::create_account(
+            payer_account.key,
+            multisig_account.key,
+            required_lamports,
+            account_space as u64,
+            program_id,
+        ),
+        &[
+            payer_account.clone(),
+            multisig_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+
+    multisig_data.serialize(&mut &mut multisig_account.data.borrow_mut()[..])?;
+
+    msg!(
+        "Multisig initialized with a {} of {} threshold",
+        multisig_data.threshold,
+        multisig_data.signers.len()
+    );
+
+    Ok(())
+}
+
+/// Initialize an empty multi-counter account
+///
+/// Accounts expected:
+/// 1. `[signer, writable]` Multi-counter account to create
+/// 2. `[signer, writable]` Payer account, which becomes the authority
+/// 3. `[]` System Program
+fn process_init_multi(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    check_account_count(accounts, CREATE_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
+
+    if counter_account.owner == program_id || !counter_account.data_is_empty() {
+        msg!(
+            "Counter account {} is already initialized",
+            counter_account.key
+        );
+        return Err(CounterError::AlreadyInitialized.into());
+    }
+
+    let counter_data = MultiCounterAccount {
+        authority: *payer_account.key,
+        counters: Vec::new(),
+    };
+    let account_space = counter_data.space();
+    let required_lamports = Rent::get()?.minimum_balance(account_space);
+
+    // Create account via CPI to System Program
+    invoke(
+        &system_instruction::create_account(
+            payer_account.key,
+            counter_account.key,
+            required_lamports,
+            account_space as u64,
+            program_id,
+        ),
+        &[
+            payer_account.clone(),
+            counter_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+
+    counter_data.serialize(&mut &mut counter_account.data.borrow_mut()[..])?;
+
+    msg!("Multi counter initialized");
+
+    Ok(())
+}
+
+/// Increment (`value` of `None`) or set the counter with `id` in a
+/// multi-counter, inserting it first if the id is new
+///
+/// New ids grow the account, with the authority topping up rent.
+///
+/// Accounts expected:
+/// 1. `[writable]` Multi-counter account to update
+/// 2. `[signer, writable]` Authority, which also pays for any growth
+/// 3. `[]` System Program
+fn process_update_multi(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    id: u32,
+    value: Option<u64>,
+) -> ProgramResult {
+    check_account_count(accounts, RESIZE_ACCOUNTS)?;
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    check_system_program(system_program)?;
+
+    // Verify ownership
+    if counter_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_writable(counter_account)?;
+
+    let mut counter_data = MultiCounterAccount::try_from_slice(&counter_account.data.borrow())
+        .map_err(|_| CounterError::NotInitialized)?;
+
+    if !authority_account.is_signer || *authority_account.key != counter_data.authority {
+        msg!("Counter authority must sign");
+        return Err(CounterError::Unauthorized.into());
+    }
+
+    let count = counter_data.entry(id);
+    *count = match value {
+        Some(value) => value,
+        None => count.checked_add(1).ok_or(CounterError::Overflow)?,
+    };
+    let count = *count;
+
+    let space = counter_data.space();
+    if space > counter_account.data_len() {
+        resize_with_rent(counter_account, authority_account, system_program, space)?;
+    }
+
+    counter_data.serialize(&mut &mut counter_account.data.borrow_mut()[..])?;
+
+    msg!("Counter {} is now: {}", id, count);
+
+    Ok(())
+}
+
+/// Verify that an account holds enough lamports to be rent-exempt at `space`
+/// bytes
+fn check_rent_exempt(account: &AccountInfo, space: usize) -> ProgramResult {
+    let rent = Rent::get()?;
+    if !rent.is_exempt(account.lamports(), space) {
+        msg!(
+            "Account {} holds {} lamports but needs {} to be rent-exempt",
+            account.key,
+            account.lamports(),
+            rent.minimum_balance(space)
+        );
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+    Ok(())
+}
+
+/// Resize an account to `len` bytes, first transferring enough lamports from
+/// `payer` to keep it rent-exempt at the new size
+fn resize_with_rent<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    len: usize,
+) -> ProgramResult {
+    let required_lamports = Rent::get()?.minimum_balance(len);
+    let top_up = required_lamports.saturating_sub(account.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, top_up),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    account.realloc(len, true)
+}
+
+/// Structured events emitted through `sol_log_data` so off-chain indexers
+/// can decode them from the `Program data:` log lines
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub enum CounterEvent {
+    /// A counter was created with the given value
+    Initialized { value: u64 },
+
+    /// A counter was incremented
+    Incremented { new_value: u64 },
+
+    /// A counter was decremented
+    Decremented { new_value: u64 },
+
+    /// A counter was overwritten
+    Set { old_value: u64, new_value: u64 },
+
+    /// A counter was reset to 0
+    Reset { old_value: u64 },
+
+    /// An amount was added to a counter
+    Added { amount: u64, new_value: u64 },
+
+    /// An amount was subtracted from a counter
+    Subtracted { amount: u64, new_value: u64 },
+
+    /// Control of a counter moved to a new authority
+    AuthorityTransferred {
+        old_authority: Pubkey,
+        new_authority: Pubkey,
+    },
+
+    /// A counter was closed and its lamports reclaimed
+    Closed { lamports: u64 },
+
+    /// A counter was multiplied by a factor
+    Multiplied {
+        factor: u64,
+        old_value: u64,
+        new_value: u64,
+    },
+
+    /// A counter was paused or unpaused
+    PauseChanged { paused: bool },
+}
+
+impl CounterEvent {
+    /// Log the Borsh-serialized event as a `Program data:` line
+    fn emit(&self) -> ProgramResult {
+        sol_log_data(&[&borsh::to_vec(self)?]);
+        Ok(())
+    }
+}
+
+/// Accounts taken by instructions that mutate an existing counter
+const AUTHORIZED_ACCOUNTS: &[&str] = &["counter (writable)", "authority (signer)"];
+
+/// Accounts taken by instructions that create a keypair-backed account
+const CREATE_ACCOUNTS: &[&str] = &[
+    "new account (signer, writable)",
+    "payer (signer, writable)",
+    "system program",
+];
+
+/// Accounts taken by instructions that create several counters, which are
+/// listed after these
+const BATCH_CREATE_ACCOUNTS: &[&str] = &["payer (signer, writable)", "system program"];
+
+/// Accounts taken by read-only instructions
+const READ_ACCOUNTS: &[&str] = &["counter"];
+
+/// Accounts taken by instructions that move value between two counters
+const TRANSFER_ACCOUNTS: &[&str] = &[
+    "source (writable)",
+    "destination (writable)",
+    "authority (signer)",
+];
+
+/// Accounts taken by instructions that may grow a counter, paid for by its
+/// authority
+const RESIZE_ACCOUNTS: &[&str] = &[
+    "counter (writable)",
+    "authority (signer, writable)",
+    "system program",
+];
+
+/// Fail up front, naming the expected accounts in order, instead of letting
+/// `next_account_info` run out partway through a handler
+fn check_account_count(accounts: &[AccountInfo], expected: &[&str]) -> ProgramResult {
+    if accounts.len() < expected.len() {
+        msg!(
+            "Expected accounts in order: {}; got {} of {}",
+            expected.join(", "),
+            accounts.len(),
+            expected.len()
+        );
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    Ok(())
+}
+
+/// Refuse a counter name that is empty, has bytes after its zero padding or
+/// is not UTF-8
+fn check_name(name: &[u8; NAME_LEN]) -> ProgramResult {
+    let len = name.iter().position(|&byte| byte == 0).unwrap_or(NAME_LEN);
+    if len == 0 {
+        msg!("Counter name must not be empty");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if name[len..].iter().any(|&byte| byte != 0) {
+        msg!("Counter name must only be followed by zero padding");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if std::str::from_utf8(&name[..len]).is_err() {
+        msg!("Counter name must be UTF-8");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok(())
+}
+
+/// Refuse a read-only counter up front, since writing it back would
+/// otherwise fail with a less obvious runtime error
+fn check_writable(counter_account: &AccountInfo) -> ProgramResult {
+    if !counter_account.is_writable {
+        msg!(
+            "Counter account {} must be passed as writable",
+            counter_account.key
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    Ok(())
+}
+
+/// Refuse a look-alike passed in place of the System Program, which would
+/// otherwise receive our create_account and transfer CPIs
+fn check_system_program(system_program: &AccountInfo) -> ProgramResult {
+    if *system_program.key != system_program::id() {
+        msg!("Expected the System Program, got {}", system_program.key);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    Ok(())
+}
+
+/// Verify that the authority account matches the authority stored in the
+/// counter and either signed the transaction or is a multisig whose
+/// threshold is met by signers anywhere in `accounts`
+fn check_authority(
+    program_id: &Pubkey,
+    counter_data: &CounterAccount,
+    authority_account: &AccountInfo,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if *authority_account.key == counter_data.authority {
+        if authority_account.is_signer {
+            return Ok(());
+        }
+
+        if authority_account.owner == program_id {
+            let data = authority_account.try_borrow_data()?;
+            if let Ok(multisig) = MultisigAccount::try_from_slice(&data) {
+                let approvals = multisig.approvals(accounts);
+                if approvals >= usize::from(multisig.threshold) {
+                    return Ok(());
+                }
+                msg!(
+                    "Multisig authority needs {} signers, got {}",
+                    multisig.threshold,
+                    approvals
+                );
+                return Err(CounterError::Unauthorized.into());
+            }
+        }
+    }
+
+    msg!("Counter authority must sign");
+    Err(CounterError::Unauthorized.into())
+}
+
+/// Errors specific to the counter program, surfaced as
+/// `ProgramError::Custom` with the variant's discriminant as the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterError {
+    /// Instruction data was empty, truncated or named an unknown variant
+    InvalidInstruction,
+
+    /// A mutation would push the counter above its maximum
+    Overflow,
+
+    /// A mutation would push the counter below its minimum
+    Underflow,
+
+    /// The counter authority did not sign, or a different account did
+    Unauthorized,
+
+    /// The counter account already holds counter data
+    AlreadyInitialized,
+
+    /// The counter account does not hold valid counter data
+    NotInitialized,
+
+    /// The counter layout version is unknown or needs migrating
+    UnsupportedVersion,
+
+    /// An increment arrived before the counter's cooldown elapsed
+    TooSoon,
+
+    /// A compare-and-set found a value other than the expected one
+    CasFailed,
+
+    /// A single call tried to add or subtract more than the counter's
+    /// `max_step`
+    StepTooLarge,
+
+    /// The counter value is frozen until the authority runs `Unpause`
+    Paused,
+
+    /// A mean was requested before any sample was added
+    NoSamples,
+
+    /// A batch is too large to finish within the compute budget
+    BatchTooLarge,
+}
+
+impl From<CounterError> for ProgramError {
+    fn from(error: CounterError) -> Self {
+        ProgramError::Custom(error as u32)
+    }
+}
+
+/// Counter account data structure
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug)]
+pub struct CounterAccount {
+    /// Layout version, always `CounterAccount::VERSION` once migrated
+    pub version: u8,
+
+    /// Current counter value
+    pub count: u64,
+
+    /// Account allowed to mutate the counter
+    pub authority: Pubkey,
+
+    /// Bump seed of the counter PDA, 0 for keypair-backed counters
+    pub bump: u8,
+
+    /// Unix timestamp of the last change, taken from the Clock sysvar
+    pub last_updated: i64,
+
+    /// Number of successful mutations, including initialization
+    pub updates: u64,
+
+    /// Amount added by each increment
+    pub step: u64,
+
+    /// Lowest value decrements may reach
+    pub min: u64,
+
+    /// Highest value increments may reach
+    pub max: u64,
+
+    /// Clamp increments and decrements at the bounds instead of failing
+    pub saturate: bool,
+
+    /// Minimum number of seconds between instructions that raise the count,
+    /// 0 for no limit
+    pub cooldown_secs: i64,
+
+    /// Unix timestamp of the last instruction that raised the count,
+    /// `i64::MIN` before the first. Tracked apart from `last_updated` so
+    /// other mutations, including initialization, don't start the cooldown.
+    pub last_incremented: i64,
+
+    /// Ring buffer of the value after each of the last `HISTORY_LEN`
+    /// mutations. Slots not written yet hold 0.
+    pub history: [u64; HISTORY_LEN],
+
+    /// Index of the `history` slot the next mutation overwrites, which is
+    /// also the oldest recorded value
+    pub history_head: u8,
+
+    /// Largest amount a single `AddToCounter`, `IncrementBy`,
+    /// `AddCheckedWithOverflowReturn`, `MultiplyCounter`, `DoubleCounter` or
+    /// incoming `TransferValue` may add, or a single `SubtractFromCounter` or
+    /// `SubtractWithDeficitReturn` may subtract, `u64::MAX` for no limit
+    pub max_step: u64,
+
+    /// Whether the authority has frozen the counter value with `Pause`
+    pub is_paused: bool,
+
+    /// Highest value the counter has held since it was created or migrated
+    pub high: u64,
+
+    /// Lowest value the counter has held since it was created or migrated
+    pub low: u64,
+
+    /// Label given by `InitializeNamedPdaCounter`, zero-padded UTF-8. All
+    /// zeros for unnamed counters.
+    pub name: [u8; NAME_LEN],
+}
+
+/// Number of past values kept in `CounterAccount::history`
+pub const HISTORY_LEN: usize = 8;
+
+/// Size in bytes of `CounterAccount::name`
+pub const NAME_LEN: usize = 16;
+
+impl CounterAccount {
+    /// Current layout version
+    pub const VERSION: u8 = 8;
+
+    /// Size of the original version 1 layout, which only held `count: u64`
+    pub const V1_LEN: usize = 8;
+
+    /// Size of the version 2 layout, which ended at `saturate`
+    pub const V2_LEN: usize = 1 + 8 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 1;
+
+    /// Size of the version 3 layout, which ended at `last_incremented`
+    pub const V3_LEN: usize = Self::V2_LEN + 8 + 8;
+
+    /// Size of the version 4 layout, which ended at `history_head`
+    pub const V4_LEN: usize = Self::V3_LEN + 8 * HISTORY_LEN + 1;
+
+    /// Size of the version 5 layout, which ended at `max_step`
+    pub const V5_LEN: usize = Self::V4_LEN + 8;
+
+    /// Size of the version 6 layout, which ended at `is_paused`
+    pub const V6_LEN: usize = Self::V5_LEN + 1;
+
+    /// Size of the version 7 layout, which ended at `low`
+    pub const V7_LEN: usize = Self::V6_LEN + 8 + 8;
+
+    /// Serialized size in bytes: version (1) + count (8) + authority (32) +
+    /// bump (1) + last_updated (8) + updates (8) + step (8) + min (8) +
+    /// max (8) + saturate (1) + cooldown_secs (8) + last_incremented (8) +
+    /// history (8 per entry) + history_head (1) + max_step (8) +
+    /// is_paused (1) + high (8) + low (8) + name (`NAME_LEN`)
+    pub const LEN: usize = Self::V7_LEN + NAME_LEN;
+
+    /// Fresh counter state with the default settings
+    fn new(initial_value: u64, authority: Pubkey) -> Result<Self, ProgramError> {
+        let CounterSettings {
+            step,
+            min,
+            max,
+            saturate,
+            cooldown_secs,
+            max_step,
+        } = CounterSettings::default();
+
+        Ok(Self {
+            version: Self::VERSION,
+            count: initial_value,
+            authority,
+            bump: 0,
+            last_updated: Clock::get()?.unix_timestamp,
+            updates: 1,
+            step,
+            min,
+            max,
+            saturate,
+            cooldown_secs,
+            last_incremented: i64::MIN,
+            history: [0; HISTORY_LEN],
+            history_head: 0,
+            max_step,
+            is_paused: false,
+            high: initial_value,
+            low: initial_value,
+            name: [0; NAME_LEN],
+        })
+    }
+
+    /// Serialized size of an older layout version that can still be migrated
+    fn legacy_len(version: u8) -> Option<usize> {
+        match version {
+            2 => Some(Self::V2_LEN),
+            3 => Some(Self::V3_LEN),
+            4 => Some(Self::V4_LEN),
+            5 => Some(Self::V5_LEN),
+            6 => Some(Self::V6_LEN),
+            7 => Some(Self::V7_LEN),
+            _ => None,
+        }
+    }
+
+    /// Refuse to raise the count until `cooldown_secs` have passed since it
+    /// last rose, then start a new cooldown
+    fn start_cooldown(&mut self) -> ProgramResult {
+        let now = Clock::get()?.unix_timestamp;
+        let ready_at = self.last_incremented.saturating_add(self.cooldown_secs);
+        if now < ready_at {
+            msg!(
+                "Counter is cooling down, next increment allowed at {} (now {})",
+                ready_at,
+                now
+            );
+            return Err(CounterError::TooSoon.into());
+        }
+
+        self.last_incremented = now;
+        Ok(())
+    }
+
+    /// Refuse to change the value of a paused counter
+    fn check_not_paused(&self) -> ProgramResult {
+        if self.is_paused {
+            msg!("Counter is paused, run Unpause first");
+            return Err(CounterError::Paused.into());
+        }
+
+        Ok(())
+    }
+
+    /// Fit a count moved by an amount into `min..=max`
+    ///
+    /// A value past either bound clamps to it when the counter saturates and
+    /// fails with `Overflow` or `Underflow` otherwise. Taking an `i128` lets
+    /// callers pass sums, differences and products that do not fit a `u64`.
+    fn bound(&self, value: i128) -> Result<u64, ProgramError> {
+        if value > i128::from(self.max) {
+            if self.saturate {
+                msg!("Counter saturated at its maximum of {}", self.max);
+                return Ok(self.max);
+            }
+            msg!("{} is above the counter maximum of {}", value, self.max);
+            return Err(CounterError::Overflow.into());
+        }
+        if value < i128::from(self.min) {
+            if self.saturate {
+                msg!("Counter saturated at its minimum of {}", self.min);
+                return Ok(self.min);
+            }
+            msg!("{} is below the counter minimum of {}", value, self.min);
+            return Err(CounterError::Underflow.into());
+        }
+
+        Ok(value as u64)
+    }
+
+    /// Refuse to write a value outside `min..=max`. Unlike amounts, values
+    /// given outright are never clamped.
+    fn check_bounds(&self, value: u64) -> ProgramResult {
+        if !(self.min..=self.max).contains(&value) {
+            msg!(
+                "Value {} must lie within the bounds {}..={}",
+                value,
+                self.min,
+                self.max
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Refuse a single call that changes the count by more than `max_step`
+    fn check_max_step(&self, amount: u64) -> ProgramResult {
+        if amount > self.max_step {
+            msg!(
+                "Cannot change the count by {} in one call, the counter allows at most {}",
+                amount,
+                self.max_step
+            );
+            return Err(CounterError::StepTooLarge.into());
+        }
+
+        Ok(())
+    }
+
+    /// The counter's name without its zero padding, empty when unnamed
+    pub fn label(&self) -> &[u8] {
+        let len = self
+            .name
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(NAME_LEN);
+        &self.name[..len]
+    }
+
+    /// History values from oldest to newest
+    pub fn recent_values(&self) -> [u64; HISTORY_LEN] {
+        let mut values = self.history;
+        values.rotate_left(usize::from(self.history_head) % HISTORY_LEN);
+        values
+    }
+
+    /// Decode counter state from account data, ignoring any spare bytes
+    /// left after the layout by `Resize`
+    ///
+    /// Empty or zeroed data has never held a counter and fails with
+    /// `CounterError::NotInitialized`, while data that fails to decode is
+    /// reported as corrupt with `ProgramError::InvalidAccountData`.
+    fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() == Self::V1_LEN {
+            msg!("Counter uses the version 1 layout, run Migrate first");
+            return Err(CounterError::UnsupportedVersion.into());
+        }
+        if data.iter().all(|&byte| byte == 0) {
+            msg!("Counter account holds no counter data, initialize it first");
+            return Err(CounterError::NotInitialized.into());
+        }
+        if let Some(&version) = data.first() {
+            if Self::legacy_len(version).is_some_and(|len| data.len() >= len) {
+                msg!("Counter uses layout version {}, run Migrate first", version);
+                return Err(CounterError::UnsupportedVersion.into());
+            }
+            if version != Self::VERSION {
+                msg!("Unsupported counter layout version {}", version);
+                return Err(CounterError::UnsupportedVersion.into());
+            }
+        }
+
+        Self::deserialize(&mut &data[..]).map_err(|_| {
+            msg!("Counter account data is corrupt");
+            ProgramError::InvalidAccountData
+        })
+    }
+
+    /// Stamp a successful mutation with the current time, bump the update
+    /// count and record the new value in the history and extremes
+    fn record_update(&mut self) -> ProgramResult {
+        self.last_updated = Clock::get()?.unix_timestamp;
+        self.updates = self.updates.checked_add(1).ok_or(CounterError::Overflow)?;
+        self.high = self.high.max(self.count);
+        self.low = self.low.min(self.count);
+
+        let head = usize::from(self.history_head) % HISTORY_LEN;
+        self.history[head] = self.count;
+        self.history_head = ((head + 1) % HISTORY_LEN) as u8;
+
+        Ok(())
+    }
+}
+
+/// Configuration chosen when a counter is created
+struct CounterSettings {
+    step: u64,
+    min: u64,
+    max: u64,
+    saturate: bool,
+    cooldown_secs: i64,
+    max_step: u64,
+}
+
+impl Default for CounterSettings {
+    /// A step of 1, no bounds, no saturation, no cooldown and no limit per
+    /// call
+    fn default() -> Self {
+        Self {
+            step: 1,
+            min: 0,
+            max: u64::MAX,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        }
+    }
+}
+
+/// Wide counter account data structure, for counts beyond `u64::MAX`
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct WideCounterAccount {
+    /// Current counter value
+    pub count: u128,
+
+    /// Account allowed to mutate the counter
+    pub authority: Pubkey,
+}
+
+impl WideCounterAccount {
+    /// Serialized size in bytes: count (16) + authority (32)
+    pub const LEN: usize = 16 + 32;
+}
+
+/// Signed counter account data structure, for values that may go negative
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct SignedCounterAccount {
+    /// Current counter value
+    pub count: i64,
+
+    /// Account allowed to mutate the counter
+    pub authority: Pubkey,
+}
+
+impl SignedCounterAccount {
+    /// Serialized size in bytes: count (8) + authority (32)
+    pub const LEN: usize = 8 + 32;
+}
+
+/// A copy of a counter's state taken by `Snapshot`
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct CounterSnapshot {
+    /// Counter the state was copied from
+    pub counter: Pubkey,
+
+    /// Unix timestamp of the snapshot, taken from the Clock sysvar
+    pub taken_at: i64,
+
+    /// Counter state at the time of the snapshot
+    pub state: CounterAccount,
+}
+
+impl CounterSnapshot {
+    /// Serialized size in bytes: counter (32) + taken_at (8) + state
+    pub const LEN: usize = 32 + 8 + CounterAccount::LEN;
+}
+
+/// Running sum and sample count, for reporting a mean
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct MeanCounterAccount {
+    /// Total of every sample added
+    pub sum: u128,
+
+    /// Number of samples added
+    pub samples: u64,
+
+    /// Account allowed to add samples
+    pub authority: Pubkey,
+}
+
+impl MeanCounterAccount {
+    /// Serialized size in bytes: sum (16) + samples (8) + authority (32)
+    pub const LEN: usize = 16 + 8 + 32;
+}
+
+/// Several counters in one account, stored as `(id, count)` pairs
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct MultiCounterAccount {
+    /// Account allowed to mutate the counters
+    pub authority: Pubkey,
+
+    /// Counters in insertion order
+    pub counters: Vec<(u32, u64)>,
+}
+
+impl MultiCounterAccount {
+    /// Serialized size in bytes of the fixed part: authority (32) + vector
+    /// length (4)
+    pub const BASE_LEN: usize = 32 + 4;
+
+    /// Serialized size in bytes of each entry: id (4) + count (8)
+    pub const ENTRY_LEN: usize = 4 + 8;
+
+    /// Serialized size in bytes of the whole account
+    pub fn space(&self) -> usize {
+        Self::BASE_LEN + self.counters.len() * Self::ENTRY_LEN
+    }
+
+    /// Count for `id`, inserting a zero entry if it does not exist yet
+    fn entry(&mut self, id: u32) -> &mut u64 {
+        let position = self
+            .counters
+            .iter()
+            .position(|(entry_id, _)| *entry_id == id);
+        let index = match position {
+            Some(index) => index,
+            None => {
+                self.counters.push((id, 0));
+                self.counters.len() - 1
+            }
+        };
+        &mut self.counters[index].1
+    }
+}
+
+/// Shared counter authority requiring `threshold` of `signers` to sign
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct MultisigAccount {
+    /// Number of distinct signers needed to approve a mutation
+    pub threshold: u8,
+
+    /// Accounts whose signatures count towards the threshold
+    pub signers: Vec<Pubkey>,
+}
+
+impl MultisigAccount {
+    /// Largest signer set a multisig may hold
+    pub const MAX_SIGNERS: usize = 11;
+
+    /// Serialized size in bytes: threshold (1) + vector length (4) + 32 per
+    /// signer
+    pub fn space(&self) -> usize {
+        1 + 4 + self.signers.len() * 32
+    }
+
+    /// Number of listed signers that signed among `accounts`
+    fn approvals(&self, accounts: &[AccountInfo]) -> usize {
+        self.signers
+            .iter()
+            .filter(|signer| {
+                accounts
+                    .iter()
+                    .any(|account| account.is_signer && account.key == *signer)
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use borsh::schema::{BorshSchemaContainer, Definition, Fields};
+    use litesvm::{types::TransactionMetadata, LiteSVM};
+    use solana_sdk::{
+        account::{Account, ReadableAccount},
+        clock::Clock,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        message::Message,
+        signature::{Keypair, Signer},
+        system_program,
+        transaction::{Transaction, TransactionError},
+    };
+
+    #[test]
+    fn test_counter_program() {
+        // Create a new instance of the Solana VM for testing
+        let mut svm = LiteSVM::new();
+
+        // Create a keypair for the transaction payer
+        let payer = Keypair::new();
+
+        // Fund the payer account with 1 SOL (1 billion lamports)
+        // This is needed to pay for transaction fees and account creation
+        svm.airdrop(&payer.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+
+            
+        // Load our compiled program into the test environment
+        let program_keypair = Keypair::new();
+        let program_id = program_keypair.pubkey();
+
+        // Load the compiled program
+        svm.add_program_from_file(
+            program_id,
+            "target/deploy/counter_program.so"
+        ).expect("Failed to load program");
+
+                // Step 1: Initialize the counter
+        let counter_keypair = Keypair::new();
+        let initial_value: u64 = 42;
+
+        println!("Testing counter initialization...");
+
+        // Use Borsh serialization for the instruction
+        let init_instruction_data = borsh::to_vec(&CounterInstruction::InitializeCounter {
+            initial_value,
+            step: 1,
+            min: 0,
+            max: u64::MAX,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        })
+        .expect("Failed to serialize instruction");
+
+        // Create the initialization instruction
+        let initialize_instruction = Instruction::new_with_bytes(
+            program_id,
+            &init_instruction_data,
+            vec![
+                // Account 1: Counter account (signer, writable)
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                // Account 2: Payer (signer, writable)
+                AccountMeta::new(payer.pubkey(), true),
+                // Account 3: System Program (not signer, not writable)
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+
+        // Build and send the transaction
+        let message = Message::new(&[initialize_instruction], Some(&payer.pubkey()));
+        let transaction = Transaction::new(
+            &[&payer, &counter_keypair],  // Signers
+            message,
+            svm.latest_blockhash()
+        );
+
+        let result = svm.send_transaction(transaction);
+        assert!(result.is_ok(), "Initialize transaction should succeed");
+
+        let logs = result.unwrap().logs;
+        println!("Transaction logs:\n{:#?}", logs);
+        assert_log_contains(&logs, "Counter initialized with value: 42");
+
+                // Check account data after initialization
+        let account = svm
+            .get_account(&counter_keypair.pubkey())
+            .expect("Failed to get counter account");
+
+        // Deserialize and verify the counter data
+        let counter: CounterAccount = CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+
+        assert_eq!(counter.count, 42);
+        println!("Counter initialized successfully with value: {}", counter.count);
+
+        // Step 2: Increment the counter
+        println!("Testing counter increment...");
+
+        // Use Borsh serialization for increment instruction
+        let increment_data = borsh::to_vec(&CounterInstruction::IncrementCounter)
+            .expect("Failed to serialize instruction");
+
+        let increment_instruction = Instruction::new_with_bytes(
+            program_id,
+            &increment_data,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                // The payer became the counter authority at initialization
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+
+        // Build and send increment transaction
+        let message = Message::new(&[increment_instruction], Some(&payer.pubkey()));
+        let transaction = Transaction::new(
+            &[&payer, &counter_keypair],
+            message,
+            svm.latest_blockhash()
+        );
+
+        let result = svm.send_transaction(transaction);
+        assert!(result.is_ok(), "Increment transaction should succeed");
+
+        let logs = result.unwrap().logs;
         println!("Transaction logs:\n{:#?}", logs);
+        assert_log_contains(&logs, "Counter incremented to: 43");
+
+        // Check account data
+        let account = svm
+            .get_account(&counter_keypair.pubkey())
+            .expect("Failed to get counter account");
+
+        let counter: CounterAccount = CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter.count, 43);
+        println!("Counter incremented successfully to: {}", counter.count);
+    }
+
+    /// Assert some program log line contains `expected`, ignoring the
+    /// runtime's invoke, consumption and success lines around it
+    fn assert_log_contains(logs: &[String], expected: &str) {
+        assert!(
+            logs.iter()
+                .filter_map(|log| log.strip_prefix("Program log: "))
+                .any(|log| log.contains(expected)),
+            "Missing {:?} in {:?}",
+            expected,
+            logs
+        );
+    }
+
+    /// Load the program into a fresh VM with a funded payer
+    fn setup() -> (LiteSVM, Keypair, Pubkey) {
+        let mut svm = LiteSVM::new();
+        let payer = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+
+        let program_id = Keypair::new().pubkey();
+        svm.add_program_from_file(program_id, "target/deploy/counter_program.so")
+            .expect("Failed to load program");
+
+        (svm, payer, program_id)
+    }
+
+    /// Send a single instruction, expiring the blockhash first so repeated
+    /// identical instructions don't get rejected as duplicates
+    fn send(
+        svm: &mut LiteSVM,
+        instruction: Instruction,
+        payer: &Keypair,
+        signers: &[&Keypair],
+    ) -> Result<TransactionMetadata, TransactionError> {
+        svm.expire_blockhash();
+        let message = Message::new(&[instruction], Some(&payer.pubkey()));
+        let mut all_signers = vec![payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new(&all_signers, message, svm.latest_blockhash());
+        svm.send_transaction(transaction)
+            .map_err(|failed| failed.err)
+    }
+
+    /// Create an unbounded counter account holding `initial_value` with a
+    /// step of 1
+    fn initialize_counter(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        payer: &Keypair,
+        initial_value: u64,
+    ) -> Keypair {
+        let init = CounterInstruction::InitializeCounter {
+            initial_value,
+            step: 1,
+            min: 0,
+            max: u64::MAX,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        initialize_counter_with(svm, program_id, payer, &init)
+            .expect("Initialize transaction should succeed")
+    }
+
+    /// Create a counter account from a fully specified initialize instruction
+    fn initialize_counter_with(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        payer: &Keypair,
+        init: &CounterInstruction,
+    ) -> Result<Keypair, TransactionError> {
+        let counter_keypair = Keypair::new();
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            init,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        send(svm, instruction, payer, &[&counter_keypair])?;
+        Ok(counter_keypair)
+    }
+
+    /// Read the current value stored in a counter account
+    fn read_count(svm: &LiteSVM, counter: &Pubkey) -> u64 {
+        let account = svm
+            .get_account(counter)
+            .expect("Failed to get counter account");
+        CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data")
+            .count
+    }
+
+    /// Replay recorded instructions against a fresh program and one counter,
+    /// checking the count after every step
+    ///
+    /// Each step is the Borsh bytes of an instruction, e.g. captured from a
+    /// devnet transaction, and the count expected once it lands. Until the
+    /// counter exists a step is sent with the create accounts, signed by the
+    /// counter and the payer; afterwards with the counter and the payer as
+    /// its authority.
+    fn replay(steps: &[(Vec<u8>, u64)]) {
+        let (mut svm, payer, program_id) = setup();
+        let counter = Keypair::new();
+
+        for (index, (data, expected)) in steps.iter().enumerate() {
+            let (accounts, signers) = if svm.get_account(&counter.pubkey()).is_none() {
+                let accounts = vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ];
+                (accounts, vec![&counter])
+            } else {
+                let accounts = vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(payer.pubkey(), true),
+                ];
+                (accounts, Vec::new())
+            };
+
+            let instruction = Instruction::new_with_bytes(program_id, data, accounts);
+            send(&mut svm, instruction, &payer, &signers)
+                .unwrap_or_else(|err| panic!("Step {} failed: {:?}", index, err));
+            assert_eq!(
+                read_count(&svm, &counter.pubkey()),
+                *expected,
+                "Count after step {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn test_replay_init_then_increment() {
+        // The flow examples/client.rs runs: initialize at 100, then increment
+        let initialize = CounterInstruction::InitializeCounter {
+            initial_value: 100,
+            step: 1,
+            min: 0,
+            max: u64::MAX,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let increment = CounterInstruction::IncrementCounter;
+        replay(&[
+            (borsh::to_vec(&initialize).unwrap(), 100),
+            (borsh::to_vec(&increment).unwrap(), 101),
+        ]);
+
+        // Raw bytes work just as well: SetCounter to 7, then Decrement
+        replay(&[
+            (borsh::to_vec(&initialize).unwrap(), 100),
+            (vec![3, 7, 0, 0, 0, 0, 0, 0, 0], 7),
+            (vec![2], 6),
+        ]);
+    }
+
+    #[test]
+    fn test_increment_uninitialized_counter() {
+        let (mut svm, payer, program_id) = setup();
+        let not_initialized = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CounterError::NotInitialized as u32),
+        );
+        let increment = CounterInstruction::IncrementCounter;
+
+        // A brand-new system account fails the owner check first
+        let fresh = Pubkey::new_unique();
+        svm.airdrop(&fresh, 1_000_000).expect("Failed to airdrop");
+        let result = send_authorized(&mut svm, program_id, &fresh, &payer, &increment);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+        );
+
+        // A program-owned account with no data at all was never initialized,
+        // whichever instruction loads it
+        let empty = Pubkey::new_unique();
+        svm.set_account(
+            empty,
+            Account {
+                lamports: 1_000_000,
+                data: vec![],
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to write empty account");
+        let result = send_authorized(&mut svm, program_id, &empty, &payer, &increment);
+        assert_eq!(result.unwrap_err(), not_initialized);
+        let decrement = CounterInstruction::DecrementCounter;
+        let result = send_authorized(&mut svm, program_id, &empty, &payer, &decrement);
+        assert_eq!(result.unwrap_err(), not_initialized);
+
+        // Room allocated for a counter but never written is zeroed
+        let zeroed = Pubkey::new_unique();
+        svm.set_account(
+            zeroed,
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(CounterAccount::LEN),
+                data: vec![0; CounterAccount::LEN],
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to write zeroed account");
+        let result = send_authorized(&mut svm, program_id, &zeroed, &payer, &increment);
+        assert_eq!(result.unwrap_err(), not_initialized);
+
+        // Current-version data that does not decode is corrupt instead
+        let counter = initialize_counter(&mut svm, program_id, &payer, 0).pubkey();
+        let mut account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        account.data.truncate(CounterAccount::LEN / 2);
+        svm.set_account(counter, account)
+            .expect("Failed to write truncated account");
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_decrement_counter() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 2);
+
+        let decrement_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::DecrementCounter,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+
+        // Decrement twice: 2 -> 1 -> 0
+        for _ in 0..2 {
+            let result = send(&mut svm, decrement_instruction.clone(), &payer, &[]);
+            assert!(result.is_ok(), "Decrement transaction should succeed");
+        }
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 0);
+
+        // A third decrement would underflow and must be rejected
+        let result = send(&mut svm, decrement_instruction, &payer, &[]);
+        assert!(result.is_err(), "Decrement below zero should fail");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 0);
+    }
+
+    #[test]
+    fn test_set_counter() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 7);
+
+        let set_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::SetCounter { value: 1_000 },
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+
+        let result = send(&mut svm, set_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Set transaction should succeed");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 1_000);
+    }
+
+    #[test]
+    fn test_reset_counter() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 42);
+
+        let reset_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Reset,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+
+        let result = send(&mut svm, reset_instruction.clone(), &payer, &[]);
+        assert!(result.is_ok(), "Reset transaction should succeed");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 0);
+
+        // Resetting an already-zero counter is a no-op success
+        let result = send(&mut svm, reset_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Resetting a zero counter should succeed");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 0);
+    }
+
+    #[test]
+    fn test_add_and_subtract_amounts() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 10);
+
+        let add_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::AddToCounter { amount: 90 },
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, add_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Add transaction should succeed");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 100);
+
+        // Subtracting more than the stored value must fail without touching it
+        let subtract_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::SubtractFromCounter { amount: 101 },
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, subtract_instruction, &payer, &[]);
+        assert!(result.is_err(), "Subtract below zero should fail");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 100);
+    }
+
+    #[test]
+    fn test_non_authority_rejected() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 5);
+
+        // A funded signer that is not the stored authority
+        let intruder = Keypair::new();
+        svm.airdrop(&intruder.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+
+        let increment_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::IncrementCounter,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(intruder.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, increment_instruction, &intruder, &[]);
+        assert!(result.is_err(), "Non-authority increment should fail");
+
+        // The real authority must also actually sign
+        let unsigned_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::IncrementCounter,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), false),
+            ],
+        );
+        let result = send(&mut svm, unsigned_instruction, &intruder, &[]);
+        assert!(result.is_err(), "Unsigned authority increment should fail");
+
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 5);
+    }
+
+    #[test]
+    fn test_transfer_authority() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 1);
+
+        let new_authority = Keypair::new();
+        svm.airdrop(&new_authority.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+
+        // Handing authority to the all-zero pubkey is rejected
+        let brick_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::TransferAuthority {
+                new_authority: Pubkey::default(),
+            },
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, brick_instruction, &payer, &[]);
+        assert!(result.is_err(), "Transfer to the zero pubkey should fail");
+
+        let transfer_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::TransferAuthority {
+                new_authority: new_authority.pubkey(),
+            },
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, transfer_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Transfer transaction should succeed");
+
+        // The old authority can no longer increment
+        let old_increment = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::IncrementCounter,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, old_increment, &payer, &[]);
+        assert!(result.is_err(), "Old authority increment should fail");
+
+        // The new authority can
+        let new_increment = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::IncrementCounter,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(new_authority.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, new_increment, &new_authority, &[]);
+        assert!(result.is_ok(), "New authority increment should succeed");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 2);
+    }
+
+    #[test]
+    fn test_close_counter() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 9);
+        let destination = Keypair::new().pubkey();
+
+        let counter_lamports = svm
+            .get_account(&counter_keypair.pubkey())
+            .expect("Failed to get counter account")
+            .lamports;
+
+        let close_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::CloseCounter,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+                AccountMeta::new(destination, false),
+            ],
+        );
+        let result = send(&mut svm, close_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Close transaction should succeed");
+
+        // The destination received the counter's rent
+        assert_eq!(svm.get_balance(&destination).unwrap_or(0), counter_lamports);
+
+        // The counter is gone (or at least empty and unfunded)
+        let closed = svm.get_account(&counter_keypair.pubkey());
+        assert!(
+            closed.is_none_or(|account| account.lamports == 0 && account.data.is_empty()),
+            "Counter account should be closed"
+        );
+    }
+
+    /// Create the PDA counter owned by `authority`
+    fn initialize_pda_counter(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        authority: &Keypair,
+        initial_value: u64,
+    ) -> Pubkey {
+        let (counter_address, _bump) = find_counter_address(&authority.pubkey(), &program_id);
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::InitializePdaCounter { initial_value },
+            vec![
+                AccountMeta::new(counter_address, false),
+                AccountMeta::new(authority.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        send(svm, instruction, authority, &[]).expect("PDA initialize should succeed");
+        counter_address
+    }
+
+    #[test]
+    fn test_pda_counters_are_deterministic() {
+        let (mut svm, alice, program_id) = setup();
+        let bob = Keypair::new();
+        svm.airdrop(&bob.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+
+        let alice_counter = initialize_pda_counter(&mut svm, program_id, &alice, 1);
+        let bob_counter = initialize_pda_counter(&mut svm, program_id, &bob, 2);
+
+        // Each authority gets its own counter at the derived address
+        assert_ne!(alice_counter, bob_counter);
+        for (authority, address, value) in [(&alice, alice_counter, 1), (&bob, bob_counter, 2)] {
+            let (expected_address, expected_bump) = Pubkey::find_program_address(
+                &[b"counter", authority.pubkey().as_ref()],
+                &program_id,
+            );
+            assert_eq!(address, expected_address);
+
+            let account = svm
+                .get_account(&address)
+                .expect("Failed to get counter account");
+            let counter = CounterAccount::try_from_slice(account.data())
+                .expect("Failed to deserialize counter data");
+            assert_eq!(counter.count, value);
+            assert_eq!(counter.authority, authority.pubkey());
+            assert_eq!(counter.bump, expected_bump);
+        }
+
+        // A PDA that doesn't match the signing authority is rejected
+        let mismatched_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::InitializePdaCounter { initial_value: 0 },
+            vec![
+                AccountMeta::new(
+                    find_counter_address(&Keypair::new().pubkey(), &program_id).0,
+                    false,
+                ),
+                AccountMeta::new(alice.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, mismatched_instruction, &alice, &[]);
+        assert!(result.is_err(), "Mismatched PDA should be rejected");
+    }
+
+    /// Zero-pad a label into a counter name
+    fn counter_name(label: &str) -> [u8; NAME_LEN] {
+        let mut name = [0; NAME_LEN];
+        name[..label.len()].copy_from_slice(label.as_bytes());
+        name
+    }
+
+    /// Create the authority's counter with the given name at its PDA
+    fn initialize_named_pda_counter(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        authority: &Keypair,
+        initial_value: u64,
+        name: [u8; NAME_LEN],
+    ) -> Result<Pubkey, TransactionError> {
+        let (counter_address, _bump) =
+            find_named_counter_address(&authority.pubkey(), &name, &program_id);
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::InitializeNamedPdaCounter {
+                initial_value,
+                name,
+            },
+            vec![
+                AccountMeta::new(counter_address, false),
+                AccountMeta::new(authority.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        send(svm, instruction, authority, &[])?;
+        Ok(counter_address)
+    }
+
+    #[test]
+    fn test_named_pda_counters() {
+        let (mut svm, payer, program_id) = setup();
+        let (daily_name, weekly_name) = (counter_name("daily"), counter_name("weekly"));
+        let daily = initialize_named_pda_counter(&mut svm, program_id, &payer, 1, daily_name)
+            .expect("Daily counter should initialize");
+        let weekly = initialize_named_pda_counter(&mut svm, program_id, &payer, 7, weekly_name)
+            .expect("Weekly counter should initialize");
+
+        // Distinct names give the same authority distinct counters, apart
+        // from its unnamed one
+        assert_ne!(daily, weekly);
+        assert_ne!(daily, find_counter_address(&payer.pubkey(), &program_id).0);
+
+        let increment = CounterInstruction::IncrementCounter;
+        assert!(send_authorized(&mut svm, program_id, &daily, &payer, &increment).is_ok());
+        assert_eq!(read_count(&svm, &daily), 2);
+        assert_eq!(read_count(&svm, &weekly), 7);
+
+        for (counter, label) in [(daily, "daily"), (weekly, "weekly")] {
+            let get_name = Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::GetName,
+                vec![AccountMeta::new_readonly(counter, false)],
+            );
+            let result =
+                send(&mut svm, get_name, &payer, &[]).expect("GetName transaction should succeed");
+            assert_eq!(result.return_data.data, label.as_bytes());
+        }
+
+        // Empty names and bytes after the padding are refused
+        let invalid_argument =
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument);
+        let result = initialize_named_pda_counter(&mut svm, program_id, &payer, 0, [0; NAME_LEN]);
+        assert_eq!(result.unwrap_err(), invalid_argument);
+        let mut gap = counter_name("a");
+        gap[2] = b'b';
+        let result = initialize_named_pda_counter(&mut svm, program_id, &payer, 0, gap);
+        assert_eq!(result.unwrap_err(), invalid_argument);
+    }
+
+    #[test]
+    fn test_get_bump() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_pda_counter(&mut svm, program_id, &payer, 0);
+
+        let get_bump = |counter| {
+            Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::GetBump,
+                vec![AccountMeta::new_readonly(counter, false)],
+            )
+        };
+        let result = send(&mut svm, get_bump(counter), &payer, &[])
+            .expect("GetBump transaction should succeed");
+        let (_address, expected_bump) =
+            Pubkey::find_program_address(&[COUNTER_SEED, payer.pubkey().as_ref()], &program_id);
+        assert_eq!(result.return_data.data, vec![expected_bump]);
+
+        // A version 1 account has no bump to report
+        let legacy = Pubkey::new_unique();
+        svm.set_account(
+            legacy,
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(CounterAccount::V1_LEN),
+                data: 42u64.to_le_bytes().to_vec(),
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to write v1 account");
+        let result = send(&mut svm, get_bump(legacy), &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::UnsupportedVersion as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_multisig_authority() {
+        let (mut svm, payer, program_id) = setup();
+        let signers = [Keypair::new(), Keypair::new(), Keypair::new()];
+        let signer_keys: Vec<Pubkey> = signers.iter().map(|signer| signer.pubkey()).collect();
+
+        let initialize_multisig = |svm: &mut LiteSVM, threshold| {
+            let multisig = Keypair::new();
+            let instruction = Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::InitializeMultisig {
+                    signers: signer_keys.clone(),
+                    threshold,
+                },
+                vec![
+                    AccountMeta::new(multisig.pubkey(), true),
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            );
+            send(svm, instruction, &payer, &[&multisig]).map(|_| multisig.pubkey())
+        };
+
+        // The threshold must be reachable and non-zero
+        for threshold in [0, 4] {
+            let result = initialize_multisig(&mut svm, threshold);
+            assert_eq!(
+                result.unwrap_err(),
+                TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+            );
+        }
+        let multisig = initialize_multisig(&mut svm, 2).expect("Multisig should initialize");
+
+        let counter = initialize_counter(&mut svm, program_id, &payer, 0).pubkey();
+        let transfer = CounterInstruction::TransferAuthority {
+            new_authority: multisig,
+        };
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &transfer).is_ok());
+
+        let increment = |approvers: &[&Keypair]| {
+            let mut accounts = vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(multisig, false),
+            ];
+            accounts.extend(
+                approvers
+                    .iter()
+                    .map(|approver| AccountMeta::new_readonly(approver.pubkey(), true)),
+            );
+            Instruction::new_with_borsh(program_id, &CounterInstruction::IncrementCounter, accounts)
+        };
+
+        // One of three signers is not enough
+        let result = send(&mut svm, increment(&[&signers[0]]), &payer, &[&signers[0]]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+        assert_eq!(read_count(&svm, &counter), 0);
+
+        // Two of three are
+        let approvers = [&signers[0], &signers[2]];
+        let result = send(&mut svm, increment(&approvers), &payer, &approvers);
+        assert!(result.is_ok(), "Increment with two signers should succeed");
+        assert_eq!(read_count(&svm, &counter), 1);
+
+        // The previous authority no longer has a say
+        let increment = CounterInstruction::IncrementCounter;
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment);
+        assert!(result.is_err(), "Old authority should be rejected");
+    }
+
+    #[test]
+    fn test_last_updated_tracks_clock() {
+        let (mut svm, payer, program_id) = setup();
+
+        let mut clock = svm.get_sysvar::<Clock>();
+        clock.unix_timestamp = 1_000;
+        svm.set_sysvar(&clock);
+
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 0);
+        let read_last_updated = |svm: &LiteSVM| {
+            let account = svm
+                .get_account(&counter_keypair.pubkey())
+                .expect("Failed to get counter account");
+            CounterAccount::try_from_slice(account.data())
+                .expect("Failed to deserialize counter data")
+                .last_updated
+        };
+        assert_eq!(read_last_updated(&svm), 1_000);
+
+        let increment_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::IncrementCounter,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, increment_instruction.clone(), &payer, &[]);
+        assert!(result.is_ok(), "First increment should succeed");
+        let first_update = read_last_updated(&svm);
+
+        // Advance the cluster clock before the second increment
+        clock.unix_timestamp += 60;
+        svm.set_sysvar(&clock);
+
+        let result = send(&mut svm, increment_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Second increment should succeed");
+        let second_update = read_last_updated(&svm);
+
+        assert_eq!(second_update, first_update + 60);
+    }
+
+    #[test]
+    fn test_increment_cooldown() {
+        let (mut svm, payer, program_id) = setup();
+
+        let mut clock = svm.get_sysvar::<Clock>();
+        clock.unix_timestamp = 1_000;
+        svm.set_sysvar(&clock);
+
+        let init = CounterInstruction::InitializeCounter {
+            initial_value: 0,
+            step: 1,
+            min: 0,
+            max: u64::MAX,
+            saturate: false,
+            cooldown_secs: 60,
+            max_step: u64::MAX,
+        };
+        let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+
+        // Initializing does not start the cooldown
+        let increment = CounterInstruction::IncrementCounter;
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment);
+        assert!(result.is_ok(), "First increment should succeed");
+
+        let too_soon = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CounterError::TooSoon as u32),
+        );
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment);
+        assert_eq!(result.unwrap_err(), too_soon);
+
+        // Other ways of raising the count are held to the same cooldown
+        let raises = [
+            CounterInstruction::AddToCounter { amount: 1 },
+            CounterInstruction::AddCheckedWithOverflowReturn { amount: 1 },
+            CounterInstruction::SetCounter { value: 5 },
+            CounterInstruction::MultiplyCounter { factor: 2 },
+        ];
+        for instruction in &raises {
+            let result = send_authorized(&mut svm, program_id, &counter, &payer, instruction);
+            assert_eq!(result.unwrap_err(), too_soon, "{:?}", instruction);
+        }
+        assert_eq!(read_count(&svm, &counter), 1);
+
+        // Keeping or lowering the count is not rate-limited
+        let multiply = CounterInstruction::MultiplyCounter { factor: 1 };
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &multiply).is_ok());
+        assert_eq!(read_count(&svm, &counter), 1);
+        let decrement = CounterInstruction::DecrementCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &decrement).is_ok());
+        assert_eq!(read_count(&svm, &counter), 0);
+
+        // Once the cooldown has elapsed the counter can move again
+        clock.unix_timestamp += 60;
+        svm.set_sysvar(&clock);
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment);
+        assert!(result.is_ok(), "Increment after cooldown should succeed");
+        assert_eq!(read_count(&svm, &counter), 1);
+    }
+
+    #[test]
+    fn test_updates_counts_every_mutation() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 3);
+
+        let authority_accounts = vec![
+            AccountMeta::new(counter_keypair.pubkey(), false),
+            AccountMeta::new_readonly(payer.pubkey(), true),
+        ];
+        for instruction in [
+            CounterInstruction::IncrementCounter,
+            CounterInstruction::IncrementCounter,
+            CounterInstruction::Reset,
+        ] {
+            let instruction =
+                Instruction::new_with_borsh(program_id, &instruction, authority_accounts.clone());
+            let result = send(&mut svm, instruction, &payer, &[]);
+            assert!(result.is_ok(), "Mutation should succeed");
+        }
+
+        let account = svm
+            .get_account(&counter_keypair.pubkey())
+            .expect("Failed to get counter account");
+        let counter = CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+
+        // init + two increments + reset
+        assert_eq!(counter.updates, 4);
+        assert_eq!(counter.count, 0);
+    }
+
+    #[test]
+    fn test_increment_emits_event() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 41);
+
+        let increment_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::IncrementCounter,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, increment_instruction, &payer, &[])
+            .expect("Increment transaction should succeed");
+
+        // Decode the base64 `Program data:` line back into an event
+        let events: Vec<CounterEvent> = result
+            .logs
+            .iter()
+            .filter_map(|line| line.strip_prefix("Program data: "))
+            .filter_map(|data| STANDARD.decode(data).ok())
+            .filter_map(|bytes| CounterEvent::try_from_slice(&bytes).ok())
+            .collect();
+        assert_eq!(events, vec![CounterEvent::Incremented { new_value: 42 }]);
+    }
+
+    #[test]
+    fn test_increment_uses_step() {
+        let (mut svm, payer, program_id) = setup();
+
+        // A zero step is rejected at initialization
+        let zero_step = CounterInstruction::InitializeCounter {
+            initial_value: 0,
+            step: 0,
+            min: 0,
+            max: u64::MAX,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let result = initialize_counter_with(&mut svm, program_id, &payer, &zero_step);
+        assert!(result.is_err(), "Zero step should be rejected");
+
+        let init = CounterInstruction::InitializeCounter {
+            initial_value: 7,
+            step: 5,
+            min: 0,
+            max: u64::MAX,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let counter_keypair = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed");
+
+        let increment_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::IncrementCounter,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        for _ in 0..2 {
+            let result = send(&mut svm, increment_instruction.clone(), &payer, &[]);
+            assert!(result.is_ok(), "Increment transaction should succeed");
+        }
+
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 7 + 10);
+    }
+
+    /// Send an instruction that only needs the counter and its authority
+    fn send_authorized(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        counter: &Pubkey,
+        authority: &Keypair,
+        instruction: &CounterInstruction,
+    ) -> Result<TransactionMetadata, TransactionError> {
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            instruction,
+            vec![
+                AccountMeta::new(*counter, false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ],
+        );
+        send(svm, instruction, authority, &[])
+    }
+
+    #[test]
+    fn test_bounds_reject_at_boundary() {
+        let (mut svm, payer, program_id) = setup();
+
+        // Initial values outside the bounds are rejected
+        let out_of_bounds = CounterInstruction::InitializeCounter {
+            initial_value: 11,
+            step: 1,
+            min: 3,
+            max: 10,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let result = initialize_counter_with(&mut svm, program_id, &payer, &out_of_bounds);
+        assert!(result.is_err(), "Out-of-bounds initial value should fail");
+
+        let init = CounterInstruction::InitializeCounter {
+            initial_value: 9,
+            step: 1,
+            min: 8,
+            max: 10,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+
+        // Up to the maximum, then no further
+        let increment = CounterInstruction::IncrementCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_ok());
+        assert_eq!(read_count(&svm, &counter), 10);
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_err());
+        assert_eq!(read_count(&svm, &counter), 10);
+
+        // Down to the minimum, then no further
+        let decrement = CounterInstruction::DecrementCounter;
+        for _ in 0..2 {
+            assert!(send_authorized(&mut svm, program_id, &counter, &payer, &decrement).is_ok());
+        }
+        assert_eq!(read_count(&svm, &counter), 8);
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &decrement).is_err());
+        assert_eq!(read_count(&svm, &counter), 8);
+    }
+
+    #[test]
+    fn test_bounds_saturate_at_boundary() {
+        let (mut svm, payer, program_id) = setup();
+
+        let init = CounterInstruction::InitializeCounter {
+            initial_value: 7,
+            step: 5,
+            min: 7,
+            max: 10,
+            saturate: true,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+
+        // 7 + 5 clamps to the maximum and stays there
+        let increment = CounterInstruction::IncrementCounter;
+        for _ in 0..2 {
+            assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_ok());
+            assert_eq!(read_count(&svm, &counter), 10);
+        }
+
+        // Decrementing from the minimum clamps instead of failing
+        let set = CounterInstruction::SetCounter { value: 7 };
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &set).is_ok());
+        let decrement = CounterInstruction::DecrementCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &decrement).is_ok());
+        assert_eq!(read_count(&svm, &counter), 7);
+    }
+
+    #[test]
+    fn test_bounds_apply_to_amounts_and_set() {
+        let (mut svm, payer, program_id) = setup();
+        let custom = |error: CounterError| {
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        };
+        let invalid_argument =
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument);
+
+        for saturate in [false, true] {
+            let init = CounterInstruction::InitializeCounter {
+                initial_value: 9,
+                step: 1,
+                min: 5,
+                max: 10,
+                saturate,
+                cooldown_secs: 0,
+                max_step: u64::MAX,
+            };
+            let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+                .expect("Initialize transaction should succeed")
+                .pubkey();
+            let update = |svm: &mut LiteSVM, instruction: CounterInstruction| {
+                send_authorized(svm, program_id, &counter, &payer, &instruction)
+            };
+
+            // Adding past the maximum errors, or clamps to it when saturating
+            let result = update(&mut svm, CounterInstruction::AddToCounter { amount: 2 });
+            if saturate {
+                assert!(result.is_ok(), "Saturating add should clamp");
+            } else {
+                assert_eq!(result.unwrap_err(), custom(CounterError::Overflow));
+                assert!(update(&mut svm, CounterInstruction::AddToCounter { amount: 1 }).is_ok());
+            }
+            assert_eq!(read_count(&svm, &counter), 10);
+
+            // Subtracting past the minimum errors, or clamps to it when saturating
+            let subtract = CounterInstruction::SubtractFromCounter { amount: 6 };
+            let result = update(&mut svm, subtract);
+            if saturate {
+                assert!(result.is_ok(), "Saturating subtract should clamp");
+            } else {
+                assert_eq!(result.unwrap_err(), custom(CounterError::Underflow));
+                let subtract = CounterInstruction::SubtractFromCounter { amount: 5 };
+                assert!(update(&mut svm, subtract).is_ok());
+            }
+            assert_eq!(read_count(&svm, &counter), 5);
+
+            // Values set outright must lie within the bounds, saturating or not
+            for value in [4, 11] {
+                let result = update(&mut svm, CounterInstruction::SetCounter { value });
+                assert_eq!(result.unwrap_err(), invalid_argument);
+            }
+            assert!(update(&mut svm, CounterInstruction::SetCounter { value: 10 }).is_ok());
+            assert_eq!(read_count(&svm, &counter), 10);
+
+            // Resetting to 0 would pass the minimum
+            let result = update(&mut svm, CounterInstruction::Reset);
+            assert_eq!(result.unwrap_err(), invalid_argument);
+            assert_eq!(read_count(&svm, &counter), 10);
+        }
+    }
+
+    #[test]
+    fn test_initialize_with_separate_authority() {
+        let (mut svm, treasury, program_id) = setup();
+        let user = Keypair::new();
+
+        let initialize = |svm: &mut LiteSVM, authority: Pubkey| {
+            let counter_keypair = Keypair::new();
+            let instruction = Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::InitializeCounter {
+                    initial_value: 0,
+                    step: 1,
+                    min: 0,
+                    max: u64::MAX,
+                    saturate: false,
+                    cooldown_secs: 0,
+                    max_step: u64::MAX,
+                },
+                vec![
+                    AccountMeta::new(counter_keypair.pubkey(), true),
+                    AccountMeta::new(treasury.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(authority, false),
+                ],
+            );
+            send(svm, instruction, &treasury, &[&counter_keypair]).map(|_| counter_keypair.pubkey())
+        };
+
+        // The System Program can never sign, so it is refused as authority
+        let result = initialize(&mut svm, system_program::id());
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+
+        // The treasury pays, but the user owns the counter
+        let counter = initialize(&mut svm, user.pubkey()).expect("Initialize should succeed");
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        let counter_data = CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.authority, user.pubkey());
+
+        let increment = CounterInstruction::IncrementCounter;
+        let result = send_authorized(&mut svm, program_id, &counter, &treasury, &increment);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &increment,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(user.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, instruction, &treasury, &[&user]);
+        assert!(result.is_ok(), "Authority increment should succeed");
+        assert_eq!(read_count(&svm, &counter), 1);
+    }
+
+    #[test]
+    fn test_mutations_require_writable_counter() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 5).pubkey();
+
+        for instruction in [
+            CounterInstruction::IncrementCounter,
+            CounterInstruction::DecrementCounter,
+            CounterInstruction::SetCounter { value: 1 },
+        ] {
+            let instruction = Instruction::new_with_borsh(
+                program_id,
+                &instruction,
+                vec![
+                    AccountMeta::new_readonly(counter, false),
+                    AccountMeta::new_readonly(payer.pubkey(), true),
+                ],
+            );
+            let result = send(&mut svm, instruction, &payer, &[]);
+            assert_eq!(
+                result.unwrap_err(),
+                TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+            );
+        }
+        assert_eq!(read_count(&svm, &counter), 5);
+    }
+
+    #[test]
+    fn test_initialize_rejects_fake_system_program() {
+        let (mut svm, payer, program_id) = setup();
+
+        let counter_keypair = Keypair::new();
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::InitializeCounter {
+                initial_value: 0,
+                step: 1,
+                min: 0,
+                max: u64::MAX,
+                saturate: false,
+                cooldown_secs: 0,
+                max_step: u64::MAX,
+            },
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            ],
+        );
+        let result = send(&mut svm, instruction, &payer, &[&counter_keypair]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+        );
+        assert!(svm.get_account(&counter_keypair.pubkey()).is_none());
+    }
+
+    #[test]
+    fn test_missing_accounts_are_named() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 0).pubkey();
+
+        let cases = [
+            (
+                CounterInstruction::IncrementCounter,
+                vec![AccountMeta::new(counter, false)],
+                "Expected accounts in order: counter (writable), authority (signer); got 1 of 2",
+            ),
+            (
+                CounterInstruction::CloseCounter,
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new_readonly(payer.pubkey(), true),
+                ],
+                "Expected accounts in order: counter (writable), authority (signer), \
+                 destination (writable); got 2 of 3",
+            ),
+            (
+                CounterInstruction::GetCounter,
+                vec![],
+                "Expected accounts in order: counter; got 0 of 1",
+            ),
+        ];
+        for (instruction, accounts, expected_log) in cases {
+            svm.expire_blockhash();
+            let instruction = Instruction::new_with_borsh(program_id, &instruction, accounts);
+            let message = Message::new(&[instruction], Some(&payer.pubkey()));
+            let transaction = Transaction::new(&[&payer], message, svm.latest_blockhash());
+            let failed = svm
+                .send_transaction(transaction)
+                .expect_err("Too few accounts should fail");
+
+            assert_eq!(
+                failed.err,
+                TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+            );
+            let logs = &failed.meta.logs;
+            assert!(
+                logs.iter().any(|log| log.ends_with(expected_log)),
+                "Missing {:?} in {:?}",
+                expected_log,
+                logs
+            );
+        }
+    }
+
+    #[test]
+    fn test_initialize_and_increment() {
+        let (mut svm, payer, program_id) = setup();
+
+        let init = CounterInstruction::InitializeAndIncrement { initial_value: 41 };
+        let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("InitializeAndIncrement transaction should succeed")
+            .pubkey();
+
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        let counter_data = CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.count, 42);
+        assert_eq!(counter_data.authority, payer.pubkey());
+        assert_eq!(counter_data.updates, 2);
+
+        // A separate authority account is refused before anything is created
+        let counter_keypair = Keypair::new();
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &init,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            ],
+        );
+        let result = send(&mut svm, instruction, &payer, &[&counter_keypair]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+        assert!(svm.get_account(&counter_keypair.pubkey()).is_none());
+    }
+
+    #[test]
+    fn test_batch_initialize() {
+        let (mut svm, payer, program_id) = setup();
+        let values = vec![1, 2, 3];
+        let counters: Vec<Keypair> = values.iter().map(|_| Keypair::new()).collect();
+
+        let mut account_metas = vec![
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ];
+        account_metas.extend(
+            counters
+                .iter()
+                .map(|counter| AccountMeta::new(counter.pubkey(), true)),
+        );
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::BatchInitialize {
+                values: values.clone(),
+            },
+            account_metas,
+        );
+        let signers: Vec<&Keypair> = counters.iter().collect();
+        send(&mut svm, instruction, &payer, &signers)
+            .expect("BatchInitialize transaction should succeed");
+
+        for (counter, value) in counters.iter().zip(values) {
+            let account = svm
+                .get_account(&counter.pubkey())
+                .expect("Failed to get counter account");
+            let counter_data = CounterAccount::try_from_slice(account.data())
+                .expect("Failed to deserialize counter data");
+            assert_eq!(counter_data.count, value);
+            assert_eq!(counter_data.authority, payer.pubkey());
+        }
+
+        // One value short of the accounts passed is rejected
+        let counter = Keypair::new();
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::BatchInitialize { values: vec![1, 2] },
+            vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(counter.pubkey(), true),
+            ],
+        );
+        let result = send(&mut svm, instruction, &payer, &[&counter]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+
+        // An oversized batch fails before looking at the counter accounts
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::BatchInitialize {
+                values: vec![0; MAX_BATCH_COUNTERS + 1],
+            },
+            vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, instruction, &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::BatchTooLarge as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_add_with_overflow_return() {
+        let (mut svm, payer, program_id) = setup();
+        let old_count = u64::MAX - 10;
+        let counter = initialize_counter(&mut svm, program_id, &payer, old_count).pubkey();
+
+        // Room to spare: everything is added and nothing comes back
+        let add = CounterInstruction::AddCheckedWithOverflowReturn { amount: 4 };
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &add)
+            .expect("Add transaction should succeed");
+        assert_eq!(return_u64(&result), 0);
+        assert_eq!(read_count(&svm, &counter), old_count + 4);
+
+        // Past the top the counter clamps and reports what was lost
+        let old_count = old_count + 4;
+        let amount = 100;
+        let add = CounterInstruction::AddCheckedWithOverflowReturn { amount };
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &add)
+            .expect("Clamped add transaction should succeed");
+        assert_eq!(return_u64(&result), amount - (u64::MAX - old_count));
+        assert_eq!(read_count(&svm, &counter), u64::MAX);
+    }
+
+    #[test]
+    fn test_subtract_with_deficit_return() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 10).pubkey();
+
+        // Enough to cover it: the whole amount is taken and nothing comes back
+        let subtract = CounterInstruction::SubtractWithDeficitReturn { amount: 5 };
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &subtract)
+            .expect("Subtract transaction should succeed");
+        assert_eq!(return_u64(&result), 0);
+        assert_eq!(read_count(&svm, &counter), 5);
+
+        // Past zero the counter drains and reports the shortfall
+        let counter = initialize_counter(&mut svm, program_id, &payer, 10).pubkey();
+        let subtract = CounterInstruction::SubtractWithDeficitReturn { amount: 30 };
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &subtract)
+            .expect("Drained subtract transaction should succeed");
+        assert_eq!(return_u64(&result), 20);
+        assert_eq!(read_count(&svm, &counter), 0);
+
+        // Only the authority can drain the counter
+        let stranger = Keypair::new();
+        svm.airdrop(&stranger.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        let result = send_authorized(&mut svm, program_id, &counter, &stranger, &subtract);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_set_from_return_data() {
+        let (mut svm, payer, program_id) = setup();
+        let calculator_id = Pubkey::new_unique();
+        svm.add_program_from_file(
+            calculator_id,
+            "../../byte-calculator/target/deploy/byte_calculator.so",
+        )
+        .expect("Failed to load calculator program");
+        let counter = initialize_counter(&mut svm, program_id, &payer, 5).pubkey();
+
+        // Calculator instruction: opcode, then two little-endian i64 operands
+        let calculate = |operation: u8, left: i64, right: i64| {
+            let mut data = vec![operation];
+            data.extend_from_slice(&left.to_le_bytes());
+            data.extend_from_slice(&right.to_le_bytes());
+            data
+        };
+        let set_from = |source: Pubkey, source_account: Pubkey, data: Vec<u8>| {
+            Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::SetFromReturnData { source, data },
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new_readonly(payer.pubkey(), true),
+                    AccountMeta::new_readonly(source_account, false),
+                ],
+            )
+        };
+
+        // The calculator multiplies 6 by 7 and the counter takes the product
+        let instruction = set_from(calculator_id, calculator_id, calculate(2, 6, 7));
+        send(&mut svm, instruction, &payer, &[]).expect("SetFromReturnData should succeed");
+        assert_eq!(read_count(&svm, &counter), 42);
+
+        // A comparison returns a single byte, which is not a count
+        let instruction = set_from(calculator_id, calculator_id, calculate(11, 1, 1));
+        let result = send(&mut svm, instruction, &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+
+        // The program account passed must be the source named in the data
+        let instruction = set_from(Pubkey::new_unique(), calculator_id, calculate(2, 6, 7));
+        let result = send(&mut svm, instruction, &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+        );
+        assert_eq!(read_count(&svm, &counter), 42);
+
+        // A returned value outside the counter bounds is refused
+        let init = CounterInstruction::InitializeCounter {
+            initial_value: 0,
+            step: 1,
+            min: 0,
+            max: 10,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let bounded = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::SetFromReturnData {
+                source: calculator_id,
+                data: calculate(2, 6, 7),
+            },
+            vec![
+                AccountMeta::new(bounded, false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+                AccountMeta::new_readonly(calculator_id, false),
+            ],
+        );
+        let result = send(&mut svm, instruction, &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+        assert_eq!(read_count(&svm, &bounded), 0);
+    }
+
+    /// Snapshot a counter into `snapshot`, which signs in case it is new
+    fn send_snapshot(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        counter: &Pubkey,
+        snapshot: &Keypair,
+        authority: &Keypair,
+    ) -> Result<TransactionMetadata, TransactionError> {
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Snapshot,
+            vec![
+                AccountMeta::new_readonly(*counter, false),
+                AccountMeta::new(snapshot.pubkey(), true),
+                AccountMeta::new(authority.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        send(svm, instruction, authority, &[snapshot])
+    }
+
+    /// Read the snapshot stored in a snapshot account
+    fn read_snapshot(svm: &LiteSVM, snapshot: &Pubkey) -> CounterSnapshot {
+        let account = svm
+            .get_account(snapshot)
+            .expect("Failed to get snapshot account");
+        CounterSnapshot::try_from_slice(account.data())
+            .expect("Failed to deserialize snapshot data")
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 0);
+        let counter = counter_keypair.pubkey();
+        let increment = || {
+            Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::IncrementCounter,
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new_readonly(payer.pubkey(), true),
+                ],
+            )
+        };
+
+        send(&mut svm, increment(), &payer, &[]).expect("Increment should succeed");
+        let snapshot = Keypair::new();
+        send_snapshot(&mut svm, program_id, &counter, &snapshot, &payer)
+            .expect("Snapshot should succeed");
+        send(&mut svm, increment(), &payer, &[]).expect("Increment should succeed");
+
+        // The snapshot keeps the value from before the second increment
+        assert_eq!(read_count(&svm, &counter), 2);
+        let taken = read_snapshot(&svm, &snapshot.pubkey());
+        assert_eq!(taken.counter, counter);
+        assert_eq!(taken.state.count, 1);
+        assert_eq!(taken.taken_at, svm.get_sysvar::<Clock>().unix_timestamp);
+
+        // Snapshotting again overwrites the same account
+        send_snapshot(&mut svm, program_id, &counter, &snapshot, &payer)
+            .expect("Second snapshot should succeed");
+        assert_eq!(read_snapshot(&svm, &snapshot.pubkey()).state.count, 2);
+
+        // Another authority can't snapshot the counter
+        let other = Keypair::new();
+        svm.airdrop(&other.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        let result = send_snapshot(&mut svm, program_id, &counter, &Keypair::new(), &other);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+    }
+
+    /// Restore a counter from a snapshot, signed by `authority`
+    fn send_restore(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        counter: &Pubkey,
+        snapshot: &Pubkey,
+        authority: &Keypair,
+    ) -> Result<TransactionMetadata, TransactionError> {
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Restore,
+            vec![
+                AccountMeta::new(*counter, false),
+                AccountMeta::new_readonly(*snapshot, false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ],
+        );
+        send(svm, instruction, authority, &[])
+    }
+
+    #[test]
+    fn test_restore() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 5).pubkey();
+        let snapshot = Keypair::new();
+        send_snapshot(&mut svm, program_id, &counter, &snapshot, &payer)
+            .expect("Snapshot should succeed");
+
+        let add = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::AddToCounter { amount: 5 },
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        send(&mut svm, add, &payer, &[]).expect("Add should succeed");
+        assert_eq!(read_count(&svm, &counter), 10);
+
+        let result = send_restore(&mut svm, program_id, &counter, &snapshot.pubkey(), &payer)
+            .expect("Restore should succeed");
+        assert_eq!(read_count(&svm, &counter), 5);
+        assert_log_contains(&result.logs, "Counter restored from 10 to 5");
+
+        // A snapshot of someone else's counter can't be restored into ours
+        let other = Keypair::new();
+        svm.airdrop(&other.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        let foreign = initialize_counter(&mut svm, program_id, &other, 99).pubkey();
+        let foreign_snapshot = Keypair::new();
+        send_snapshot(&mut svm, program_id, &foreign, &foreign_snapshot, &other)
+            .expect("Snapshot should succeed");
+        let result = send_restore(
+            &mut svm,
+            program_id,
+            &counter,
+            &foreign_snapshot.pubkey(),
+            &payer,
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+        assert_eq!(read_count(&svm, &counter), 5);
+
+        // A value outside our bounds is refused even from our own snapshot
+        let init = CounterInstruction::InitializeCounter {
+            initial_value: 0,
+            step: 1,
+            min: 0,
+            max: 4,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let bounded = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+        let result = send_restore(&mut svm, program_id, &bounded, &snapshot.pubkey(), &payer);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+        assert_eq!(read_count(&svm, &bounded), 0);
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 5).pubkey();
+
+        // A stale expected value is rejected and reports the current count
+        svm.expire_blockhash();
+        let stale = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::CompareAndSet {
+                expected: 4,
+                new: 9,
+            },
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+            ],
+        );
+        let message = Message::new(&[stale], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, svm.latest_blockhash());
+        let failed = svm
+            .send_transaction(transaction)
+            .expect_err("Stale compare-and-set should fail");
+        assert_eq!(
+            failed.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::CasFailed as u32)
+            )
+        );
+        assert_eq!(return_u64(&failed.meta), 5);
+        assert_eq!(read_count(&svm, &counter), 5);
+
+        // The current value lets the swap through
+        let swap = CounterInstruction::CompareAndSet {
+            expected: 5,
+            new: 9,
+        };
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &swap);
+        assert!(result.is_ok(), "Compare-and-set transaction should succeed");
+        assert_eq!(read_count(&svm, &counter), 9);
+
+        // Only the authority may swap
+        let intruder = Keypair::new();
+        svm.airdrop(&intruder.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        let swap = CounterInstruction::CompareAndSet {
+            expected: 9,
+            new: 0,
+        };
+        let result = send_authorized(&mut svm, program_id, &counter, &intruder, &swap);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+        assert_eq!(read_count(&svm, &counter), 9);
+
+        // The new value must lie within the counter bounds
+        let init = CounterInstruction::InitializeCounter {
+            initial_value: 5,
+            step: 1,
+            min: 0,
+            max: 10,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let bounded = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+        let swap = CounterInstruction::CompareAndSet {
+            expected: 5,
+            new: 11,
+        };
+        let result = send_authorized(&mut svm, program_id, &bounded, &payer, &swap);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+        assert_eq!(read_count(&svm, &bounded), 5);
+    }
+
+    #[test]
+    fn test_multiply_counter() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 3).pubkey();
+
+        let multiply = CounterInstruction::MultiplyCounter { factor: 10 };
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &multiply);
+        assert!(result.is_ok(), "Multiply transaction should succeed");
+        assert_eq!(read_count(&svm, &counter), 30);
+
+        let double = CounterInstruction::DoubleCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &double).is_ok());
+        assert_eq!(read_count(&svm, &counter), 60);
+
+        // Near the top of the range the product overflows and nothing changes
+        let set = CounterInstruction::SetCounter {
+            value: u64::MAX / 2 + 1,
+        };
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &set).is_ok());
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &double);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Overflow as u32)
+            )
+        );
+        assert_eq!(read_count(&svm, &counter), u64::MAX / 2 + 1);
+    }
+
+    #[test]
+    fn test_decrement_floor() {
+        let (mut svm, payer, program_id) = setup();
+
+        // A floor above the initial value is rejected
+        let below_floor = CounterInstruction::InitializeCounter {
+            initial_value: 9,
+            step: 1,
+            min: 10,
+            max: u64::MAX,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let result = initialize_counter_with(&mut svm, program_id, &payer, &below_floor);
+        assert!(result.is_err(), "Initial value below the floor should fail");
+
+        let decrement = CounterInstruction::DecrementCounter;
+        for saturate in [false, true] {
+            let init = CounterInstruction::InitializeCounter {
+                initial_value: 12,
+                step: 1,
+                min: 10,
+                max: u64::MAX,
+                saturate,
+                cooldown_secs: 0,
+                max_step: u64::MAX,
+            };
+            let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+                .expect("Initialize transaction should succeed")
+                .pubkey();
+
+            for _ in 0..2 {
+                let result = send_authorized(&mut svm, program_id, &counter, &payer, &decrement);
+                assert!(result.is_ok(), "Decrement above the floor should succeed");
+            }
+            assert_eq!(read_count(&svm, &counter), 10);
+
+            // Going below the floor errors, or clamps to it when saturating
+            let result = send_authorized(&mut svm, program_id, &counter, &payer, &decrement);
+            if saturate {
+                assert!(result.is_ok(), "Saturating decrement should clamp");
+            } else {
+                assert_eq!(
+                    result.unwrap_err(),
+                    TransactionError::InstructionError(
+                        0,
+                        InstructionError::Custom(CounterError::Underflow as u32)
+                    )
+                );
+            }
+            assert_eq!(read_count(&svm, &counter), 10);
+        }
+    }
+
+    #[test]
+    fn test_saturating_increment_stops_at_u64_max() {
+        let (mut svm, payer, program_id) = setup();
+
+        let init = CounterInstruction::InitializeCounter {
+            initial_value: u64::MAX - 1,
+            step: 1,
+            min: 0,
+            max: u64::MAX,
+            saturate: true,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+
+        let increment = CounterInstruction::IncrementCounter;
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment)
+            .expect("First increment should succeed");
+        assert_eq!(read_count(&svm, &counter), u64::MAX);
+        assert!(!result.logs.iter().any(|log| log.contains("saturated")));
+
+        // The second increment would overflow, so it saturates and says so
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment)
+            .expect("Saturating increment should succeed");
+        assert_eq!(read_count(&svm, &counter), u64::MAX);
+        assert!(result
+            .logs
+            .iter()
+            .any(|log| log.contains("Counter saturated at its maximum")));
+    }
+
+    /// Decode little-endian `u64` return data
+    fn return_u64(metadata: &TransactionMetadata) -> u64 {
+        let bytes: [u8; 8] = metadata
+            .return_data
+            .data
+            .as_slice()
+            .try_into()
+            .expect("Return data should be 8 bytes");
+        u64::from_le_bytes(bytes)
+    }
+
+    #[test]
+    fn test_count_returned_as_return_data() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = initialize_counter(&mut svm, program_id, &payer, 99);
+
+        // Increment hands back the new value
+        let increment = CounterInstruction::IncrementCounter;
+        let result = send_authorized(
+            &mut svm,
+            program_id,
+            &counter_keypair.pubkey(),
+            &payer,
+            &increment,
+        )
+        .expect("Increment transaction should succeed");
+        assert_eq!(result.return_data.program_id, program_id);
+        assert_eq!(return_u64(&result), 100);
+
+        // GetCounter reads without mutating
+        let get_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::GetCounter,
+            vec![AccountMeta::new_readonly(counter_keypair.pubkey(), false)],
+        );
+        let result =
+            send(&mut svm, get_instruction, &payer, &[]).expect("Get transaction should succeed");
+        assert_eq!(return_u64(&result), 100);
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 100);
+    }
+
+    #[test]
+    fn test_get_counters() {
+        let (mut svm, payer, program_id) = setup();
+        let counters: Vec<Pubkey> = [5, 0, 42]
+            .into_iter()
+            .map(|value| initialize_counter(&mut svm, program_id, &payer, value).pubkey())
+            .collect();
+
+        // Room allocated for a counter but never written is skipped
+        let zeroed = Pubkey::new_unique();
+        svm.set_account(
+            zeroed,
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(CounterAccount::LEN),
+                data: vec![0; CounterAccount::LEN],
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to write zeroed account");
+
+        let accounts = [counters[0], zeroed, counters[1], counters[2]]
+            .iter()
+            .map(|counter| AccountMeta::new_readonly(*counter, false))
+            .collect();
+        let get_counters =
+            Instruction::new_with_borsh(program_id, &CounterInstruction::GetCounters, accounts);
+        let result = send(&mut svm, get_counters, &payer, &[]).expect("GetCounters should succeed");
+        let skipped = format!("Skipping uninitialized counter {}", zeroed);
+        assert_log_contains(&result.logs, &skipped);
+
+        let values = Vec::<u64>::try_from_slice(&result.return_data.data)
+            .expect("Return data should be a length-prefixed list");
+        assert_eq!(values, vec![5, 0, 42]);
+
+        // Accounts of other programs are refused outright
+        let foreign = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::GetCounters,
+            vec![
+                AccountMeta::new_readonly(counters[0], false),
+                AccountMeta::new_readonly(payer.pubkey(), false),
+            ],
+        );
+        let result = send(&mut svm, foreign, &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+        );
+    }
+
+    /// Ask the program whether a proof places the counter's value under `root`
+    fn verify_count_proof(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        payer: &Keypair,
+        counter: &Pubkey,
+        proof: Vec<[u8; 32]>,
+        root: [u8; 32],
+    ) -> bool {
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::VerifyCountProof { proof, root },
+            vec![AccountMeta::new_readonly(*counter, false)],
+        );
+        let result = send(svm, instruction, payer, &[]).expect("VerifyCountProof should succeed");
+        match result.return_data.data[..] {
+            [verified] => verified == 1,
+            ref data => panic!("Expected a single byte, got {:?}", data),
+        }
+    }
+
+    #[test]
+    fn test_merkle_parent_ignores_order() {
+        let (a, b) = (count_leaf(1), count_leaf(2));
+        assert_eq!(merkle_parent(&a, &b), merkle_parent(&b, &a));
+        assert_ne!(merkle_parent(&a, &b), merkle_parent(&a, &a));
+        assert_ne!(count_leaf(1), count_leaf(2));
+    }
+
+    #[test]
+    fn test_verify_count_proof() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 42).pubkey();
+
+        //          root
+        //        /      \
+        //     ab          cd
+        //    /  \        /  \
+        //  42    7      9    11
+        let leaves = [42, 7, 9, 11].map(count_leaf);
+        let ab = merkle_parent(&leaves[0], &leaves[1]);
+        let cd = merkle_parent(&leaves[2], &leaves[3]);
+        let root = merkle_parent(&ab, &cd);
+
+        let proof = vec![leaves[1], cd];
+        let verified =
+            verify_count_proof(&mut svm, program_id, &payer, &counter, proof.clone(), root);
+        assert!(verified, "A valid proof should verify");
+
+        // A wrong sibling leads to a different root
+        let wrong_sibling = vec![leaves[2], cd];
+        let verified =
+            verify_count_proof(&mut svm, program_id, &payer, &counter, wrong_sibling, root);
+        assert!(!verified, "A proof with a wrong sibling should fail");
+
+        // The proof covers 42, so it fails once the counter moves on
+        let increment = CounterInstruction::IncrementCounter;
+        send_authorized(&mut svm, program_id, &counter, &payer, &increment)
+            .expect("Increment should succeed");
+        let verified = verify_count_proof(&mut svm, program_id, &payer, &counter, proof, root);
+        assert!(!verified, "The proof should not cover the new value");
+    }
+
+    #[test]
+    fn test_get_parity() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 42).pubkey();
+        let get_parity = |counter: Pubkey| {
+            Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::GetParity,
+                vec![AccountMeta::new_readonly(counter, false)],
+            )
+        };
+
+        let result = send(&mut svm, get_parity(counter), &payer, &[])
+            .expect("GetParity transaction should succeed");
+        assert_eq!(result.return_data.data, vec![0]);
+
+        let increment = CounterInstruction::IncrementCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_ok());
+        let result = send(&mut svm, get_parity(counter), &payer, &[])
+            .expect("GetParity transaction should succeed");
+        assert_eq!(result.return_data.data, vec![1]);
+        assert_eq!(read_count(&svm, &counter), 43);
+
+        // An account without counter data has no parity
+        let empty = Pubkey::new_unique();
+        svm.set_account(
+            empty,
+            Account {
+                lamports: 1_000_000,
+                data: vec![],
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to write empty account");
+        let result = send(&mut svm, get_parity(empty), &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::NotInitialized as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_initialize_or_get_is_idempotent() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = Keypair::new();
+
+        let initialize_or_get = |initial_value| {
+            Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::InitializeOrGet { initial_value },
+                vec![
+                    AccountMeta::new(counter_keypair.pubkey(), true),
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+
+        // First call creates the counter
+        let instruction = initialize_or_get(10);
+        let result = send(&mut svm, instruction, &payer, &[&counter_keypair]);
+        assert!(result.is_ok(), "First InitializeOrGet should succeed");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 10);
+
+        // Second call with a different value is a no-op
+        let instruction = initialize_or_get(500);
+        let result = send(&mut svm, instruction, &payer, &[&counter_keypair]);
+        assert!(result.is_ok(), "Second InitializeOrGet should succeed");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 10);
+
+        // A counter resized past the current layout still counts as existing
+        let mut account = svm
+            .get_account(&counter_keypair.pubkey())
+            .expect("Failed to get counter account");
+        account.data.resize(CounterAccount::LEN + 32, 0);
+        account.lamports = svm.minimum_balance_for_rent_exemption(account.data.len());
+        svm.set_account(counter_keypair.pubkey(), account)
+            .expect("Failed to write resized account");
+        let instruction = initialize_or_get(500);
+        let result = send(&mut svm, instruction, &payer, &[&counter_keypair]);
+        assert!(result.is_ok(), "Resized counter should be reused");
+        assert_eq!(read_count(&svm, &counter_keypair.pubkey()), 10);
+
+        // So does a version 1 counter that has not been migrated yet
+        let legacy = Account {
+            lamports: svm.minimum_balance_for_rent_exemption(CounterAccount::V1_LEN),
+            data: 10u64.to_le_bytes().to_vec(),
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        };
+        svm.set_account(counter_keypair.pubkey(), legacy.clone())
+            .expect("Failed to write version 1 account");
+        let instruction = initialize_or_get(500);
+        let result = send(&mut svm, instruction, &payer, &[&counter_keypair]);
+        assert!(result.is_ok(), "Version 1 counter should be reused");
+        let account = svm
+            .get_account(&counter_keypair.pubkey())
+            .expect("Failed to get counter account");
+        assert_eq!(account.data, legacy.data);
+    }
+
+    #[test]
+    fn test_empty_instruction_data_rejected() {
+        let (mut svm, payer, program_id) = setup();
+
+        let empty_instruction = Instruction::new_with_bytes(program_id, &[], vec![]);
+        let result = send(&mut svm, empty_instruction, &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidInstruction as u32)
+            )
+        );
+
+        // Truncated data for a known variant fails the same way
+        let truncated_instruction = Instruction::new_with_bytes(program_id, &[3, 1, 2], vec![]);
+        let result = send(&mut svm, truncated_instruction, &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidInstruction as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_instruction_discriminants_are_stable() {
+        // The first byte of every instruction is its variant's position in
+        // the enum. Deployed clients hardcode these, so the list below may
+        // only ever grow at the end; an existing entry must never change.
+        let cases: Vec<(&[u8], CounterInstruction)> = vec![
+            (
+                &[0],
+                CounterInstruction::InitializeCounter {
+                    initial_value: 0,
+                    step: 1,
+                    min: 0,
+                    max: u64::MAX,
+                    saturate: false,
+                    cooldown_secs: 0,
+                    max_step: u64::MAX,
+                },
+            ),
+            (&[1], CounterInstruction::IncrementCounter),
+            (&[2], CounterInstruction::DecrementCounter),
+            (
+                &[3, 7, 0, 0, 0, 0, 0, 0, 0],
+                CounterInstruction::SetCounter { value: 7 },
+            ),
+            (&[4], CounterInstruction::Reset),
+            (&[5], CounterInstruction::AddToCounter { amount: 0 }),
+            (&[6], CounterInstruction::SubtractFromCounter { amount: 0 }),
+            (
+                &[7],
+                CounterInstruction::TransferAuthority {
+                    new_authority: Pubkey::default(),
+                },
+            ),
+            (&[8], CounterInstruction::CloseCounter),
+            (
+                &[9],
+                CounterInstruction::InitializePdaCounter { initial_value: 0 },
+            ),
+            (&[10], CounterInstruction::GetCounter),
+            (
+                &[11],
+                CounterInstruction::InitializeOrGet { initial_value: 0 },
+            ),
+            (
+                &[12],
+                CounterInstruction::InitializeWideCounter { initial_value: 0 },
+            ),
+            (&[13], CounterInstruction::IncrementWideCounter),
+            (&[14], CounterInstruction::Migrate),
+            (&[15], CounterInstruction::IncrementBy { times: 0 }),
+            (&[16], CounterInstruction::InitMulti),
+            (&[17], CounterInstruction::IncrementId { id: 0 }),
+            (&[18], CounterInstruction::SetId { id: 0, value: 0 }),
+            (&[19], CounterInstruction::GetBump),
+            (
+                &[20],
+                CounterInstruction::InitializeMultisig {
+                    signers: Vec::new(),
+                    threshold: 0,
+                },
+            ),
+            (&[21], CounterInstruction::Resize { new_len: 0 }),
+            (&[22], CounterInstruction::GetHistory),
+            (
+                &[23],
+                CounterInstruction::InitializeSignedCounter { initial_value: 0 },
+            ),
+            (&[24], CounterInstruction::IncrementSignedCounter),
+            (&[25], CounterInstruction::DecrementSignedCounter),
+            (&[26], CounterInstruction::MultiplyCounter { factor: 0 }),
+            (&[27], CounterInstruction::DoubleCounter),
+            (
+                &[28],
+                CounterInstruction::InitializeAndIncrement { initial_value: 0 },
+            ),
+            (
+                &[29],
+                CounterInstruction::AddCheckedWithOverflowReturn { amount: 0 },
+            ),
+            (
+                &[30],
+                CounterInstruction::CompareAndSet {
+                    expected: 0,
+                    new: 0,
+                },
+            ),
+            (&[31], CounterInstruction::Pause),
+            (&[32], CounterInstruction::Unpause),
+            (&[33], CounterInstruction::TransferValue { amount: 0 }),
+            (
+                &[34, 0, 0, 0, 0],
+                CounterInstruction::BatchInitialize { values: Vec::new() },
+            ),
+            (
+                &[35],
+                CounterInstruction::SubtractWithDeficitReturn { amount: 0 },
+            ),
+            (
+                &[36],
+                CounterInstruction::SetFromReturnData {
+                    source: Pubkey::default(),
+                    data: Vec::new(),
+                },
+            ),
+            (&[37], CounterInstruction::Snapshot),
+            (&[38], CounterInstruction::Restore),
+            (&[39], CounterInstruction::InitializeMeanCounter),
+            (
+                &[40, 7, 0, 0, 0, 0, 0, 0, 0],
+                CounterInstruction::AddSample { value: 7 },
+            ),
+            (&[41], CounterInstruction::GetMean),
+            (&[42], CounterInstruction::GetParity),
+            (&[43], CounterInstruction::GetExtremes),
+            (
+                &[44, 7, 0, 0, 0, 0, 0, 0, 0, b'a'],
+                CounterInstruction::InitializeNamedPdaCounter {
+                    initial_value: 7,
+                    name: *b"a\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+                },
+            ),
+            (&[45], CounterInstruction::GetName),
+            (&[46], CounterInstruction::GetCounters),
+            (
+                &[47, 0, 0, 0, 0],
+                CounterInstruction::VerifyCountProof {
+                    proof: Vec::new(),
+                    root: [0; 32],
+                },
+            ),
+        ];
+
+        for (prefix, variant) in &cases {
+            let bytes = borsh::to_vec(variant).expect("Failed to serialize instruction");
+            assert!(
+                bytes.starts_with(prefix),
+                "{:?} encodes as {:?}, expected it to start with {:?}",
+                variant,
+                bytes,
+                prefix
+            );
+        }
+
+        // Fieldless variants are nothing but their tag
+        assert_eq!(
+            borsh::to_vec(&CounterInstruction::IncrementCounter).unwrap(),
+            [1]
+        );
+
+        // A variant added without a case here would go unchecked
+        assert_eq!(CounterInstruction::min_len(cases.len() as u8), None);
+    }
+
+    #[test]
+    fn test_min_len_matches_encoding() {
+        let variants = [
+            CounterInstruction::InitializeCounter {
+                initial_value: 0,
+                step: 1,
+                min: 0,
+                max: u64::MAX,
+                saturate: false,
+                cooldown_secs: 0,
+                max_step: u64::MAX,
+            },
+            CounterInstruction::IncrementCounter,
+            CounterInstruction::DecrementCounter,
+            CounterInstruction::SetCounter { value: 0 },
+            CounterInstruction::Reset,
+            CounterInstruction::AddToCounter { amount: 0 },
+            CounterInstruction::SubtractFromCounter { amount: 0 },
+            CounterInstruction::TransferAuthority {
+                new_authority: Pubkey::default(),
+            },
+            CounterInstruction::CloseCounter,
+            CounterInstruction::InitializePdaCounter { initial_value: 0 },
+            CounterInstruction::GetCounter,
+            CounterInstruction::InitializeOrGet { initial_value: 0 },
+            CounterInstruction::InitializeWideCounter { initial_value: 0 },
+            CounterInstruction::IncrementWideCounter,
+            CounterInstruction::Migrate,
+            CounterInstruction::IncrementBy { times: 0 },
+            CounterInstruction::InitMulti,
+            CounterInstruction::IncrementId { id: 0 },
+            CounterInstruction::SetId { id: 0, value: 0 },
+            CounterInstruction::GetBump,
+            CounterInstruction::InitializeMultisig {
+                signers: Vec::new(),
+                threshold: 0,
+            },
+            CounterInstruction::Resize { new_len: 0 },
+            CounterInstruction::GetHistory,
+            CounterInstruction::InitializeSignedCounter { initial_value: 0 },
+            CounterInstruction::IncrementSignedCounter,
+            CounterInstruction::DecrementSignedCounter,
+            CounterInstruction::MultiplyCounter { factor: 0 },
+            CounterInstruction::DoubleCounter,
+            CounterInstruction::InitializeAndIncrement { initial_value: 0 },
+            CounterInstruction::AddCheckedWithOverflowReturn { amount: 0 },
+            CounterInstruction::CompareAndSet {
+                expected: 0,
+                new: 0,
+            },
+            CounterInstruction::Pause,
+            CounterInstruction::Unpause,
+            CounterInstruction::TransferValue { amount: 0 },
+            CounterInstruction::BatchInitialize { values: Vec::new() },
+            CounterInstruction::SubtractWithDeficitReturn { amount: 0 },
+            CounterInstruction::SetFromReturnData {
+                source: Pubkey::default(),
+                data: Vec::new(),
+            },
+            CounterInstruction::Snapshot,
+            CounterInstruction::Restore,
+            CounterInstruction::InitializeMeanCounter,
+            CounterInstruction::AddSample { value: 0 },
+            CounterInstruction::GetMean,
+            CounterInstruction::GetParity,
+            CounterInstruction::GetExtremes,
+            CounterInstruction::InitializeNamedPdaCounter {
+                initial_value: 0,
+                name: [0; NAME_LEN],
+            },
+            CounterInstruction::GetName,
+            CounterInstruction::GetCounters,
+            CounterInstruction::VerifyCountProof {
+                proof: Vec::new(),
+                root: [0; 32],
+            },
+        ];
+
+        for variant in &variants {
+            let bytes = borsh::to_vec(variant).expect("Failed to serialize instruction");
+            assert_eq!(
+                CounterInstruction::min_len(bytes[0]),
+                Some(bytes.len()),
+                "{:?}",
+                variant
+            );
+        }
+        assert_eq!(CounterInstruction::min_len(variants.len() as u8), None);
+    }
+
+    #[test]
+    fn test_custom_error_codes() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, u64::MAX).pubkey();
+        let custom = |error: CounterError| {
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        };
+
+        // Overflow past u64::MAX
+        let increment = CounterInstruction::IncrementCounter;
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment);
+        assert_eq!(result.unwrap_err(), custom(CounterError::Overflow));
+        assert_eq!(CounterError::Overflow as u32, 1);
+
+        // Underflow below zero
+        let reset = CounterInstruction::Reset;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &reset).is_ok());
+        let decrement = CounterInstruction::DecrementCounter;
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &decrement);
+        assert_eq!(result.unwrap_err(), custom(CounterError::Underflow));
+
+        // Some other signer
+        let intruder = Keypair::new();
+        svm.airdrop(&intruder.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        let result = send_authorized(&mut svm, program_id, &counter, &intruder, &increment);
+        assert_eq!(result.unwrap_err(), custom(CounterError::Unauthorized));
+    }
+
+    #[test]
+    fn test_wide_counter_round_trips_above_u64() {
+        let counter = WideCounterAccount {
+            count: u64::MAX as u128 + 12_345,
+            authority: Pubkey::new_unique(),
+        };
+        let bytes = borsh::to_vec(&counter).expect("Failed to serialize counter");
+        assert_eq!(bytes.len(), WideCounterAccount::LEN);
+        assert_eq!(
+            WideCounterAccount::try_from_slice(&bytes).expect("Failed to deserialize counter"),
+            counter
+        );
+    }
+
+    #[test]
+    fn test_wide_counter_overflow() {
+        let (mut svm, payer, program_id) = setup();
+        let init = CounterInstruction::InitializeWideCounter {
+            initial_value: u128::MAX - 1,
+        };
+        let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+
+        // One step below the ceiling still fits
+        let increment = CounterInstruction::IncrementWideCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_ok());
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        let counter_data = WideCounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.count, u128::MAX);
+
+        // The next increment overflows and is rejected
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Overflow as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_signed_counter_round_trips_negative() {
+        for count in [-1, i64::MIN, -12_345] {
+            let counter = SignedCounterAccount {
+                count,
+                authority: Pubkey::new_unique(),
+            };
+            let bytes = borsh::to_vec(&counter).expect("Failed to serialize counter");
+            assert_eq!(bytes.len(), SignedCounterAccount::LEN);
+            assert_eq!(
+                SignedCounterAccount::try_from_slice(&bytes)
+                    .expect("Failed to deserialize counter"),
+                counter
+            );
+        }
+    }
+
+    #[test]
+    fn test_signed_counter_crosses_zero() {
+        let (mut svm, payer, program_id) = setup();
+        let init = CounterInstruction::InitializeSignedCounter { initial_value: 1 };
+        let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+        let read_signed = |svm: &LiteSVM| {
+            let account = svm
+                .get_account(&counter)
+                .expect("Failed to get counter account");
+            SignedCounterAccount::try_from_slice(account.data())
+                .expect("Failed to deserialize counter data")
+                .count
+        };
+
+        let decrement = CounterInstruction::DecrementSignedCounter;
+        for expected in [0, -1, -2] {
+            assert!(send_authorized(&mut svm, program_id, &counter, &payer, &decrement).is_ok());
+            assert_eq!(read_signed(&svm), expected);
+        }
+
+        let increment = CounterInstruction::IncrementSignedCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_ok());
+        assert_eq!(read_signed(&svm), -1);
+
+        // The i64 range still bounds the counter
+        let init = CounterInstruction::InitializeSignedCounter {
+            initial_value: i64::MIN,
+        };
+        let floor = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+        let result = send_authorized(&mut svm, program_id, &floor, &payer, &decrement);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Underflow as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_mean_counter() {
+        let (mut svm, payer, program_id) = setup();
+        let init = CounterInstruction::InitializeMeanCounter;
+        let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+        let get_mean = || {
+            Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::GetMean,
+                vec![AccountMeta::new_readonly(counter, false)],
+            )
+        };
+
+        // No mean before the first sample
+        let result = send(&mut svm, get_mean(), &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::NoSamples as u32)
+            )
+        );
+
+        for value in [10, 20, 30] {
+            let add = CounterInstruction::AddSample { value };
+            assert!(send_authorized(&mut svm, program_id, &counter, &payer, &add).is_ok());
+        }
+        let result = send(&mut svm, get_mean(), &payer, &[]).expect("GetMean should succeed");
+        assert_eq!(return_u64(&result), 20);
+
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        let counter_data = MeanCounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.sum, 60);
+        assert_eq!(counter_data.samples, 3);
+    }
+
+    #[test]
+    fn test_migrate_v1_counter() {
+        let (mut svm, payer, program_id) = setup();
+
+        // Write a version 1 (count-only) account by hand
+        let counter_keypair = Keypair::new();
+        let counter = counter_keypair.pubkey();
+        svm.set_account(
+            counter,
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(CounterAccount::V1_LEN),
+                data: 42u64.to_le_bytes().to_vec(),
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to write v1 account");
+
+        // Mutations refuse the old layout until it is migrated
+        let increment = CounterInstruction::IncrementCounter;
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::UnsupportedVersion as u32)
+            )
+        );
+
+        // A stranger without the counter keypair can't claim the counter
+        let stranger = Keypair::new();
+        svm.airdrop(&stranger.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        let claim = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Migrate,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(stranger.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, claim, &stranger, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            svm.get_account(&counter)
+                .expect("Failed to get counter account")
+                .data
+                .len(),
+            CounterAccount::V1_LEN
+        );
+
+        let migrate_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Migrate,
+            vec![
+                AccountMeta::new(counter, true),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(
+            &mut svm,
+            migrate_instruction.clone(),
+            &payer,
+            &[&counter_keypair],
+        );
+        assert!(result.is_ok(), "Migrate transaction should succeed");
+
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        assert_eq!(account.data.len(), CounterAccount::LEN);
+        assert!(account.lamports >= svm.minimum_balance_for_rent_exemption(CounterAccount::LEN));
+
+        let counter_data = CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.version, CounterAccount::VERSION);
+        assert_eq!(counter_data.count, 42);
+        assert_eq!(counter_data.authority, payer.pubkey());
+        assert_eq!(counter_data.step, 1);
+        assert_eq!(counter_data.min, 0);
+        assert_eq!(counter_data.max, u64::MAX);
+        assert!(!counter_data.saturate);
+        assert_eq!(counter_data.cooldown_secs, 0);
+        assert_eq!(counter_data.last_incremented, i64::MIN);
+
+        // Migrating again is a no-op
+        let result = send(&mut svm, migrate_instruction, &payer, &[&counter_keypair]);
+        assert!(result.is_ok(), "Repeated migrate should succeed");
+        assert_eq!(read_count(&svm, &counter), 42);
+
+        // And the migrated counter works as usual
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_ok());
+        assert_eq!(read_count(&svm, &counter), 43);
+    }
+
+    #[test]
+    fn test_migrate_v2_counter() {
+        let (mut svm, payer, program_id) = setup();
+
+        // Rewrite a fresh counter as the version 2 layout, which predates the
+        // cooldown fields
+        let counter = initialize_counter(&mut svm, program_id, &payer, 7).pubkey();
+        let mut account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        account.data.truncate(CounterAccount::V2_LEN);
+        account.data[0] = 2;
+        svm.set_account(counter, account)
+            .expect("Failed to write v2 account");
+
+        let migrate_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Migrate,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, migrate_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Migrate transaction should succeed");
+
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        assert_eq!(account.data.len(), CounterAccount::LEN);
+        let counter_data = CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.version, CounterAccount::VERSION);
+        assert_eq!(counter_data.count, 7);
+        assert_eq!(counter_data.authority, payer.pubkey());
+        assert_eq!(counter_data.cooldown_secs, 0);
+        assert_eq!(counter_data.last_incremented, i64::MIN);
+    }
+
+    #[test]
+    fn test_migrate_v3_counter() {
+        let (mut svm, payer, program_id) = setup();
+
+        // Version 3 predates the history, and may carry spare room from Resize
+        for spare in [0, 100] {
+            let counter = initialize_counter(&mut svm, program_id, &payer, 7).pubkey();
+            let mut account = svm
+                .get_account(&counter)
+                .expect("Failed to get counter account");
+            account.data.truncate(CounterAccount::V3_LEN);
+            account.data.resize(CounterAccount::V3_LEN + spare, 0);
+            account.data[0] = 3;
+            svm.set_account(counter, account)
+                .expect("Failed to write v3 account");
+
+            // Mutations point at Migrate until it has run
+            let increment = CounterInstruction::IncrementCounter;
+            let result = send_authorized(&mut svm, program_id, &counter, &payer, &increment);
+            assert_eq!(
+                result.unwrap_err(),
+                TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(CounterError::UnsupportedVersion as u32)
+                )
+            );
+
+            let migrate_instruction = Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::Migrate,
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            );
+            let result = send(&mut svm, migrate_instruction, &payer, &[]);
+            assert!(result.is_ok(), "Migrate transaction should succeed");
+
+            let account = svm
+                .get_account(&counter)
+                .expect("Failed to get counter account");
+            let new_len = CounterAccount::LEN.max(CounterAccount::V3_LEN + spare);
+            assert_eq!(account.data.len(), new_len);
+            assert!(account.lamports >= svm.minimum_balance_for_rent_exemption(new_len));
+            let counter_data = CounterAccount::deserialize(&mut account.data())
+                .expect("Failed to deserialize counter data");
+            assert_eq!(counter_data.version, CounterAccount::VERSION);
+            assert_eq!(counter_data.count, 7);
+            assert_eq!(counter_data.history, [0; HISTORY_LEN]);
+            assert_eq!(counter_data.history_head, 0);
+        }
+    }
+
+    #[test]
+    fn test_migrate_v4_counter() {
+        let (mut svm, payer, program_id) = setup();
+
+        // Version 4 predates the per-call limit
+        let counter = initialize_counter(&mut svm, program_id, &payer, 7).pubkey();
+        let mut account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        account.data.truncate(CounterAccount::V4_LEN);
+        account.data[0] = 4;
+        svm.set_account(counter, account)
+            .expect("Failed to write v4 account");
+
+        let migrate_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Migrate,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, migrate_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Migrate transaction should succeed");
 
-                // Check account data after initialization
         let account = svm
-            .get_account(&counter_keypair.pubkey())
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        assert_eq!(account.data.len(), CounterAccount::LEN);
+        let counter_data = CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.version, CounterAccount::VERSION);
+        assert_eq!(counter_data.count, 7);
+        assert_eq!(counter_data.history[0], 7);
+        assert_eq!(counter_data.max_step, u64::MAX);
+        assert!(!counter_data.is_paused);
+    }
+
+    #[test]
+    fn test_migrate_v6_counter() {
+        let (mut svm, payer, program_id) = setup();
+
+        // Version 6 predates the high and low marks
+        let counter = initialize_counter(&mut svm, program_id, &payer, 7).pubkey();
+        let mut account = svm
+            .get_account(&counter)
             .expect("Failed to get counter account");
+        account.data.truncate(CounterAccount::V6_LEN);
+        account.data[0] = 6;
+        svm.set_account(counter, account)
+            .expect("Failed to write v6 account");
 
-        // Deserialize and verify the counter data
-        let counter: CounterAccount = CounterAccount::try_from_slice(account.data())
+        let migrate_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Migrate,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, migrate_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Migrate transaction should succeed");
+
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        assert_eq!(account.data.len(), CounterAccount::LEN);
+        let counter_data = CounterAccount::try_from_slice(account.data())
             .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.version, CounterAccount::VERSION);
+        assert_eq!(counter_data.count, 7);
+        assert_eq!(counter_data.high, 7);
+        assert_eq!(counter_data.low, 7);
+    }
 
-        assert_eq!(counter.count, 42);
-        println!("Counter initialized successfully with value: {}", counter.count);
+    #[test]
+    fn test_migrate_v7_counter() {
+        let (mut svm, payer, program_id) = setup();
 
-        // Step 2: Increment the counter
-        println!("Testing counter increment...");
+        // Version 7 predates names but already tracked extremes
+        let counter = initialize_counter(&mut svm, program_id, &payer, 7).pubkey();
+        let set = CounterInstruction::SetCounter { value: 30 };
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &set).is_ok());
+        let mut account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        account.data.truncate(CounterAccount::V7_LEN);
+        account.data[0] = 7;
+        svm.set_account(counter, account)
+            .expect("Failed to write v7 account");
 
-        // Use Borsh serialization for increment instruction
-        let increment_data = borsh::to_vec(&CounterInstruction::IncrementCounter)
-            .expect("Failed to serialize instruction");
+        let migrate_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Migrate,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, migrate_instruction, &payer, &[]);
+        assert!(result.is_ok(), "Migrate transaction should succeed");
 
-        let increment_instruction = Instruction::new_with_bytes(
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        assert_eq!(account.data.len(), CounterAccount::LEN);
+        let counter_data = CounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.version, CounterAccount::VERSION);
+        assert_eq!(counter_data.count, 30);
+        assert_eq!(counter_data.high, 30);
+        assert_eq!(counter_data.low, 7);
+        assert_eq!(counter_data.name, [0; NAME_LEN]);
+    }
+
+    #[test]
+    fn test_extremes_track_every_mutation() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 10).pubkey();
+
+        for value in [5, 20, 3, 12] {
+            let set = CounterInstruction::SetCounter { value };
+            assert!(send_authorized(&mut svm, program_id, &counter, &payer, &set).is_ok());
+        }
+
+        let get_extremes = Instruction::new_with_borsh(
             program_id,
-            &increment_data,
-            vec![AccountMeta::new(counter_keypair.pubkey(), true)],
+            &CounterInstruction::GetExtremes,
+            vec![AccountMeta::new_readonly(counter, false)],
         );
+        let result = send(&mut svm, get_extremes, &payer, &[]).expect("GetExtremes should succeed");
+        let (high, low) = result.return_data.data.split_at(8);
+        let high: [u8; 8] = high.try_into().expect("High should be 8 bytes");
+        let low: [u8; 8] = low.try_into().expect("Low should be 8 bytes");
+        assert_eq!(u64::from_le_bytes(high), 20);
+        assert_eq!(u64::from_le_bytes(low), 3);
+        assert_eq!(read_count(&svm, &counter), 12);
+    }
 
-        // Build and send increment transaction
-        let message = Message::new(&[increment_instruction], Some(&payer.pubkey()));
-        let transaction = Transaction::new(
-            &[&payer, &counter_keypair],
-            message,
-            svm.latest_blockhash()
+    /// Send a TransferValue between two counters signed by `authority`
+    fn send_transfer_value(
+        svm: &mut LiteSVM,
+        program_id: Pubkey,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Keypair,
+        amount: u64,
+    ) -> Result<TransactionMetadata, TransactionError> {
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::TransferValue { amount },
+            vec![
+                AccountMeta::new(*source, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ],
         );
+        send(svm, instruction, authority, &[])
+    }
 
-        let result = svm.send_transaction(transaction);
-        assert!(result.is_ok(), "Increment transaction should succeed");
+    #[test]
+    fn test_transfer_value() {
+        let (mut svm, payer, program_id) = setup();
+        let source = initialize_counter(&mut svm, program_id, &payer, 50).pubkey();
+        let destination = initialize_counter(&mut svm, program_id, &payer, 0).pubkey();
 
-        let logs = result.unwrap().logs;
-        println!("Transaction logs:\n{:#?}", logs);
+        let result = send_transfer_value(&mut svm, program_id, &source, &destination, &payer, 30);
+        assert!(result.is_ok(), "TransferValue transaction should succeed");
+        assert_eq!(read_count(&svm, &source), 20);
+        assert_eq!(read_count(&svm, &destination), 30);
+
+        // Moving more than the source holds changes neither side
+        let result = send_transfer_value(&mut svm, program_id, &source, &destination, &payer, 21);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Underflow as u32)
+            )
+        );
+        assert_eq!(read_count(&svm, &source), 20);
+        assert_eq!(read_count(&svm, &destination), 30);
+
+        // A counter under another authority can't be drained into
+        let other = Keypair::new();
+        svm.airdrop(&other.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        let foreign = initialize_counter(&mut svm, program_id, &other, 0).pubkey();
+        let result = send_transfer_value(&mut svm, program_id, &source, &foreign, &payer, 5);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+        assert_eq!(read_count(&svm, &source), 20);
+    }
+
+    #[test]
+    fn test_transfer_value_respects_bounds() {
+        let (mut svm, payer, program_id) = setup();
+        let custom = |error: CounterError| {
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        };
+
+        // Both counters saturate, which must not let a transfer clamp
+        let source_init = CounterInstruction::InitializeCounter {
+            initial_value: 30,
+            step: 1,
+            min: 15,
+            max: u64::MAX,
+            saturate: true,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let source = initialize_counter_with(&mut svm, program_id, &payer, &source_init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+        let destination_init = CounterInstruction::InitializeCounter {
+            initial_value: 0,
+            step: 1,
+            min: 0,
+            max: 10,
+            saturate: true,
+            cooldown_secs: 0,
+            max_step: u64::MAX,
+        };
+        let destination = initialize_counter_with(&mut svm, program_id, &payer, &destination_init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+
+        let result = send_transfer_value(&mut svm, program_id, &source, &destination, &payer, 16);
+        assert_eq!(result.unwrap_err(), custom(CounterError::Underflow));
+        let result = send_transfer_value(&mut svm, program_id, &source, &destination, &payer, 11);
+        assert_eq!(result.unwrap_err(), custom(CounterError::Overflow));
+        assert_eq!(read_count(&svm, &source), 30);
+        assert_eq!(read_count(&svm, &destination), 0);
+
+        // Up to both bounds at once is fine
+        let result = send_transfer_value(&mut svm, program_id, &source, &destination, &payer, 10);
+        assert!(result.is_ok(), "TransferValue transaction should succeed");
+        assert_eq!(read_count(&svm, &source), 20);
+        assert_eq!(read_count(&svm, &destination), 10);
+    }
+
+    #[test]
+    fn test_pause_freezes_mutations() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 7).pubkey();
+        let custom = |error: CounterError| {
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        };
+
+        // Only the authority may pause
+        let intruder = Keypair::new();
+        svm.airdrop(&intruder.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        let pause = CounterInstruction::Pause;
+        let result = send_authorized(&mut svm, program_id, &counter, &intruder, &pause);
+        assert_eq!(result.unwrap_err(), custom(CounterError::Unauthorized));
+
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &pause).is_ok());
+        let frozen = [
+            CounterInstruction::IncrementCounter,
+            CounterInstruction::DecrementCounter,
+            CounterInstruction::SetCounter { value: 1 },
+        ];
+        for instruction in &frozen {
+            let result = send_authorized(&mut svm, program_id, &counter, &payer, instruction);
+            assert_eq!(
+                result.unwrap_err(),
+                custom(CounterError::Paused),
+                "{:?}",
+                instruction
+            );
+        }
+        assert_eq!(read_count(&svm, &counter), 7);
+
+        // Reads still work while paused
+        let get = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::GetCounter,
+            vec![AccountMeta::new_readonly(counter, false)],
+        );
+        let result = send(&mut svm, get, &payer, &[]).expect("Get transaction should succeed");
+        assert_eq!(return_u64(&result), 7);
+
+        let unpause = CounterInstruction::Unpause;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &unpause).is_ok());
+        let increment = CounterInstruction::IncrementCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_ok());
+        assert_eq!(read_count(&svm, &counter), 8);
+    }
+
+    #[test]
+    fn test_max_step() {
+        let (mut svm, payer, program_id) = setup();
+        let init = CounterInstruction::InitializeCounter {
+            initial_value: 0,
+            step: 1,
+            min: 0,
+            max: u64::MAX,
+            saturate: false,
+            cooldown_secs: 0,
+            max_step: 5,
+        };
+        let counter = initialize_counter_with(&mut svm, program_id, &payer, &init)
+            .expect("Initialize transaction should succeed")
+            .pubkey();
+        let too_large = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CounterError::StepTooLarge as u32),
+        );
+
+        // Exactly the limit is fine
+        let add = CounterInstruction::AddToCounter { amount: 5 };
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &add).is_ok());
+        let increment_by = CounterInstruction::IncrementBy { times: 5 };
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment_by).is_ok());
+        assert_eq!(read_count(&svm, &counter), 10);
+
+        // One past it is refused by every amount-taking instruction
+        let over_limit = [
+            CounterInstruction::AddToCounter { amount: 6 },
+            CounterInstruction::IncrementBy { times: 6 },
+            CounterInstruction::AddCheckedWithOverflowReturn { amount: 6 },
+        ];
+        for instruction in &over_limit {
+            let result = send_authorized(&mut svm, program_id, &counter, &payer, instruction);
+            assert_eq!(result.unwrap_err(), too_large, "{:?}", instruction);
+        }
+        assert_eq!(read_count(&svm, &counter), 10);
+
+        // Subtracting is held to the same limit
+        let over_limit = [
+            CounterInstruction::SubtractFromCounter { amount: 6 },
+            CounterInstruction::SubtractWithDeficitReturn { amount: 6 },
+        ];
+        for instruction in &over_limit {
+            let result = send_authorized(&mut svm, program_id, &counter, &payer, instruction);
+            assert_eq!(result.unwrap_err(), too_large, "{:?}", instruction);
+        }
+        assert_eq!(read_count(&svm, &counter), 10);
+        let subtract = CounterInstruction::SubtractFromCounter { amount: 5 };
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &subtract).is_ok());
+        assert_eq!(read_count(&svm, &counter), 5);
+
+        // Multiplying is limited by how much the product adds
+        let multiply = CounterInstruction::MultiplyCounter { factor: 3 };
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &multiply);
+        assert_eq!(result.unwrap_err(), too_large);
+        let double = CounterInstruction::DoubleCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &double).is_ok());
+        assert_eq!(read_count(&svm, &counter), 10);
+    }
+
+    #[test]
+    fn test_get_history() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 0).pubkey();
+
+        let increment = CounterInstruction::IncrementCounter;
+        for _ in 0..10 {
+            assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_ok());
+        }
+
+        let get_history = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::GetHistory,
+            vec![AccountMeta::new_readonly(counter, false)],
+        );
+        let result = send(&mut svm, get_history, &payer, &[])
+            .expect("GetHistory transaction should succeed");
+
+        // The two oldest increments have been overwritten
+        let values: Vec<u64> = result
+            .return_data
+            .data
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(values, (3..=10).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_resize_counter() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 5).pubkey();
+
+        let resize = |new_len| {
+            Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::Resize { new_len },
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+
+        // Grow by 48 bytes, topping up rent from the authority
+        let new_len = CounterAccount::LEN + 48;
+        let result = send(&mut svm, resize(new_len as u64), &payer, &[]);
+        assert!(result.is_ok(), "Resize transaction should succeed");
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        assert_eq!(account.data.len(), new_len);
+        assert!(account.lamports >= svm.minimum_balance_for_rent_exemption(new_len));
+
+        // The spare bytes don't get in the way of using the counter
+        let increment = CounterInstruction::IncrementCounter;
+        assert!(send_authorized(&mut svm, program_id, &counter, &payer, &increment).is_ok());
+        let account = svm
+            .get_account(&counter)
+            .expect("Failed to get counter account");
+        let counter_data = CounterAccount::deserialize(&mut account.data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(counter_data.count, 6);
+
+        // Shrinking back to the layout size is fine, below it is not
+        let layout_len = CounterAccount::LEN as u64;
+        let result = send(&mut svm, resize(layout_len), &payer, &[]);
+        assert!(result.is_ok(), "Shrinking to the layout should succeed");
+        let result = send(&mut svm, resize(layout_len - 1), &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+
+        // Only the authority may resize
+        let stranger = Keypair::new();
+        svm.airdrop(&stranger.pubkey(), 1_000_000_000)
+            .expect("Failed to airdrop");
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Resize {
+                new_len: new_len as u64,
+            },
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(stranger.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, instruction, &stranger, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_migrate_refuses_downgrade() {
+        let (mut svm, payer, program_id) = setup();
+
+        // A counter claiming a layout newer than this program knows about
+        let counter = Pubkey::new_unique();
+        let mut data = vec![0; CounterAccount::LEN];
+        data[0] = CounterAccount::VERSION + 1;
+        svm.set_account(
+            counter,
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(CounterAccount::LEN),
+                data,
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to write account");
+
+        let migrate_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::Migrate,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, migrate_instruction, &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::UnsupportedVersion as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn test_increment_by() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 5).pubkey();
+
+        let bulk = CounterInstruction::IncrementBy { times: 1_000_000 };
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &bulk);
+        assert!(result.is_ok(), "IncrementBy transaction should succeed");
+        assert_eq!(read_count(&svm, &counter), 1_000_005);
+
+        // Zero increments is rejected outright
+        let none = CounterInstruction::IncrementBy { times: 0 };
+        let result = send_authorized(&mut svm, program_id, &counter, &payer, &none);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+
+        // A base near u64::MAX overflows and leaves the count untouched
+        let near_max = initialize_counter(&mut svm, program_id, &payer, u64::MAX - 10).pubkey();
+        let result = send_authorized(&mut svm, program_id, &near_max, &payer, &bulk);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Overflow as u32)
+            )
+        );
+        assert_eq!(read_count(&svm, &near_max), u64::MAX - 10);
+    }
+
+    #[test]
+    fn test_multi_counter_ids_are_isolated() {
+        let (mut svm, payer, program_id) = setup();
+
+        let counter_keypair = Keypair::new();
+        let init_instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::InitMulti,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, init_instruction, &payer, &[&counter_keypair]);
+        assert!(result.is_ok(), "InitMulti transaction should succeed");
+
+        let update = |svm: &mut LiteSVM, instruction: &CounterInstruction| {
+            let instruction = Instruction::new_with_borsh(
+                program_id,
+                instruction,
+                vec![
+                    AccountMeta::new(counter_keypair.pubkey(), false),
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            );
+            send(svm, instruction, &payer, &[])
+        };
+
+        // Increment three ids a different number of times
+        for (id, times) in [(7, 1), (42, 2), (1_000, 3)] {
+            for _ in 0..times {
+                let result = update(&mut svm, &CounterInstruction::IncrementId { id });
+                assert!(result.is_ok(), "IncrementId transaction should succeed");
+            }
+        }
+        let result = update(&mut svm, &CounterInstruction::SetId { id: 42, value: 500 });
+        assert!(result.is_ok(), "SetId transaction should succeed");
 
-        // Check account data
         let account = svm
             .get_account(&counter_keypair.pubkey())
             .expect("Failed to get counter account");
+        let counter_data = MultiCounterAccount::try_from_slice(account.data())
+            .expect("Failed to deserialize multi counter");
+        assert_eq!(counter_data.authority, payer.pubkey());
+        assert_eq!(counter_data.counters, vec![(7, 1), (42, 500), (1_000, 3)]);
 
-        let counter: CounterAccount = CounterAccount::try_from_slice(account.data())
+        // The account grew with each new id and stays rent-exempt
+        assert_eq!(account.data.len(), counter_data.space());
+        assert!(account.lamports >= svm.minimum_balance_for_rent_exemption(account.data.len()));
+    }
+
+    #[test]
+    fn test_get_counter_refuses_writable_empty_account() {
+        let (mut svm, payer, program_id) = setup();
+        let counter = initialize_counter(&mut svm, program_id, &payer, 7).pubkey();
+
+        let get = |counter_meta| {
+            Instruction::new_with_borsh(
+                program_id,
+                &CounterInstruction::GetCounter,
+                vec![counter_meta],
+            )
+        };
+
+        let before = CounterAccount::try_from_slice(svm.get_account(&counter).unwrap().data())
             .expect("Failed to deserialize counter data");
-        assert_eq!(counter.count, 43);
-        println!("Counter incremented successfully to: {}", counter.count);
+        let instruction = get(AccountMeta::new_readonly(counter, false));
+        let result =
+            send(&mut svm, instruction, &payer, &[]).expect("Get transaction should succeed");
+        assert_eq!(return_u64(&result), 7);
+
+        // Reading leaves the stored state untouched, update count included
+        let after = CounterAccount::try_from_slice(svm.get_account(&counter).unwrap().data())
+            .expect("Failed to deserialize counter data");
+        assert_eq!(after.count, before.count);
+        assert_eq!(after.updates, before.updates);
+        assert_eq!(after.last_updated, before.last_updated);
+
+        // A counter passed writable can still be read
+        let result = send(&mut svm, get(AccountMeta::new(counter, false)), &payer, &[])
+            .expect("Get with a writable counter should succeed");
+        assert_eq!(return_u64(&result), 7);
+
+        // A writable account with no data is refused
+        let empty = Pubkey::new_unique();
+        let result = send(&mut svm, get(AccountMeta::new(empty, false)), &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+
+        // So is counter data owned by another program
+        let foreign = Pubkey::new_unique();
+        let mut account = svm.get_account(&counter).unwrap();
+        account.owner = system_program::id();
+        svm.set_account(foreign, account)
+            .expect("Failed to write foreign account");
+        let instruction = get(AccountMeta::new_readonly(foreign, false));
+        let result = send(&mut svm, instruction, &payer, &[]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn test_underfunded_counter_rejected() {
+        let (mut svm, payer, program_id) = setup();
+
+        // A counter-sized, program-owned account funded below the rent minimum
+        let counter_keypair = Keypair::new();
+        svm.set_account(
+            counter_keypair.pubkey(),
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(CounterAccount::LEN) - 1,
+                data: vec![0; CounterAccount::LEN],
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .expect("Failed to write underfunded account");
+
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::InitializeOrGet { initial_value: 1 },
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, instruction, &payer, &[&counter_keypair]);
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::AccountNotRentExempt)
+        );
+    }
+
+    #[test]
+    fn test_initialize_logs_rent_and_space() {
+        let (mut svm, payer, program_id) = setup();
+        let counter_keypair = Keypair::new();
+
+        let instruction = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::InitializeCounter {
+                initial_value: 0,
+                step: 1,
+                min: 0,
+                max: u64::MAX,
+                saturate: false,
+                cooldown_secs: 0,
+                max_step: u64::MAX,
+            },
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = send(&mut svm, instruction, &payer, &[&counter_keypair])
+            .expect("Initialize transaction should succeed");
+
+        // The fee is charged before the program runs, so the logged balance is
+        // the payer's final balance
+        let rent = svm.minimum_balance_for_rent_exemption(CounterAccount::LEN);
+        let remaining = svm.get_balance(&payer.pubkey()).unwrap();
+        let expected = format!(
+            "Program log: Allocated {} bytes for {} lamports of rent, payer has {} lamports left",
+            CounterAccount::LEN,
+            rent,
+            remaining
+        );
+        assert!(
+            result.logs.contains(&expected),
+            "Missing {:?} in {:?}",
+            expected,
+            result.logs
+        );
+    }
+
+    /// Ceiling on the compute units any single counter instruction may use.
+    /// Every instruction sits far below it today, so crossing it flags a
+    /// costly change such as a heavier event.
+    const MAX_COMPUTE_UNITS: u64 = 50_000;
+
+    #[test]
+    fn test_compute_units_per_instruction() {
+        let (mut svm, payer, program_id) = setup();
+
+        let counter_keypair = Keypair::new();
+        let counter = counter_keypair.pubkey();
+        let initialize = Instruction::new_with_borsh(
+            program_id,
+            &CounterInstruction::InitializeCounter {
+                initial_value: 100,
+                step: 1,
+                min: 0,
+                max: u64::MAX,
+                saturate: false,
+                cooldown_secs: 0,
+                max_step: u64::MAX,
+            },
+            vec![
+                AccountMeta::new(counter, true),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let meta = send(&mut svm, initialize, &payer, &[&counter_keypair])
+            .expect("Initialize transaction should succeed");
+        let mut costs = vec![("InitializeCounter", meta.compute_units_consumed)];
+
+        let cases = [
+            ("IncrementCounter", CounterInstruction::IncrementCounter),
+            ("DecrementCounter", CounterInstruction::DecrementCounter),
+            ("SetCounter", CounterInstruction::SetCounter { value: 50 }),
+            ("Reset", CounterInstruction::Reset),
+            (
+                "AddToCounter",
+                CounterInstruction::AddToCounter { amount: 10 },
+            ),
+            (
+                "SubtractFromCounter",
+                CounterInstruction::SubtractFromCounter { amount: 5 },
+            ),
+            ("IncrementBy", CounterInstruction::IncrementBy { times: 10 }),
+            (
+                "MultiplyCounter",
+                CounterInstruction::MultiplyCounter { factor: 3 },
+            ),
+            ("DoubleCounter", CounterInstruction::DoubleCounter),
+            (
+                "AddCheckedWithOverflowReturn",
+                CounterInstruction::AddCheckedWithOverflowReturn { amount: 1 },
+            ),
+            (
+                "SubtractWithDeficitReturn",
+                CounterInstruction::SubtractWithDeficitReturn { amount: 1 },
+            ),
+        ];
+        for (name, instruction) in cases {
+            let meta = send_authorized(&mut svm, program_id, &counter, &payer, &instruction)
+                .unwrap_or_else(|err| panic!("{} failed: {:?}", name, err));
+            costs.push((name, meta.compute_units_consumed));
+        }
+
+        // Reads take the counter read-only
+        let reads = [
+            ("GetCounter", CounterInstruction::GetCounter),
+            ("GetHistory", CounterInstruction::GetHistory),
+        ];
+        for (name, instruction) in reads {
+            let read = Instruction::new_with_borsh(
+                program_id,
+                &instruction,
+                vec![AccountMeta::new_readonly(counter, false)],
+            );
+            let meta = send(&mut svm, read, &payer, &[])
+                .unwrap_or_else(|err| panic!("{} failed: {:?}", name, err));
+            costs.push((name, meta.compute_units_consumed));
+        }
+
+        println!("{:<30} {:>14}", "Instruction", "Compute units");
+        for (name, units) in costs {
+            println!("{:<30} {:>14}", name, units);
+            assert!(
+                units <= MAX_COMPUTE_UNITS,
+                "{} used {} compute units",
+                name,
+                units
+            );
+        }
+    }
+
+    #[test]
+    fn test_account_schema_field_order() {
+        let schema = BorshSchemaContainer::for_type::<CounterAccount>();
+        let Some(Definition::Struct {
+            fields: Fields::NamedFields(fields),
+        }) = schema.get_definition("CounterAccount")
+        else {
+            panic!("CounterAccount should have a struct schema");
+        };
+
+        let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "version",
+                "count",
+                "authority",
+                "bump",
+                "last_updated",
+                "updates",
+                "step",
+                "min",
+                "max",
+                "saturate",
+                "cooldown_secs",
+                "last_incremented",
+                "history",
+                "history_head",
+                "max_step",
+                "is_paused",
+                "high",
+                "low",
+                "name",
+            ]
+        );
+        assert_eq!(
+            borsh::max_serialized_size::<CounterAccount>().expect("Layout is bounded"),
+            CounterAccount::LEN
+        );
+    }
+
+    #[test]
+    fn test_instruction_schema_variant_order() {
+        let schema = BorshSchemaContainer::for_type::<CounterInstruction>();
+        let Some(Definition::Enum { variants, .. }) = schema.get_definition("CounterInstruction")
+        else {
+            panic!("CounterInstruction should have an enum schema");
+        };
+
+        // Tags are part of the wire format, so variants may only be appended
+        let names: Vec<&str> = variants.iter().map(|(_, name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "InitializeCounter",
+                "IncrementCounter",
+                "DecrementCounter",
+                "SetCounter",
+                "Reset",
+                "AddToCounter",
+                "SubtractFromCounter",
+                "TransferAuthority",
+                "CloseCounter",
+                "InitializePdaCounter",
+                "GetCounter",
+                "InitializeOrGet",
+                "InitializeWideCounter",
+                "IncrementWideCounter",
+                "Migrate",
+                "IncrementBy",
+                "InitMulti",
+                "IncrementId",
+                "SetId",
+                "GetBump",
+                "InitializeMultisig",
+                "Resize",
+                "GetHistory",
+                "InitializeSignedCounter",
+                "IncrementSignedCounter",
+                "DecrementSignedCounter",
+                "MultiplyCounter",
+                "DoubleCounter",
+                "InitializeAndIncrement",
+                "AddCheckedWithOverflowReturn",
+                "CompareAndSet",
+                "Pause",
+                "Unpause",
+                "TransferValue",
+                "BatchInitialize",
+                "SubtractWithDeficitReturn",
+                "SetFromReturnData",
+                "Snapshot",
+                "Restore",
+                "InitializeMeanCounter",
+                "AddSample",
+                "GetMean",
+                "GetParity",
+                "GetExtremes",
+                "InitializeNamedPdaCounter",
+                "GetName",
+                "GetCounters",
+                "VerifyCountProof",
+            ]
+        );
+        for (tag, (discriminant, name, _)) in variants.iter().enumerate() {
+            assert_eq!(*discriminant, tag as i64, "{}", name);
+        }
     }
-}
\ No newline at end of file
+}